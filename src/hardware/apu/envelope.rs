@@ -0,0 +1,62 @@
+// NRx2: initial volume in bits 7-4, direction in bit 3, period in bits 2-0
+pub struct Envelope {
+    initial: u8,
+    increase: bool,
+    period: u8,
+    pub volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    pub fn new() -> Self {
+        Envelope {
+            initial: 0,
+            increase: false,
+            period: 0,
+            volume: 0,
+            timer: 0,
+        }
+    }
+
+    pub fn read(&self) -> u8 {
+        self.initial << 4 | (self.increase as u8) << 3 | self.period
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.initial = value >> 4;
+        self.increase = value & 0b1000 != 0;
+        self.period = value & 0b111;
+    }
+
+    // the channel's DAC is off when the upper 5 bits of NRx2 are clear
+    pub fn is_dac_enabled(&self) -> bool {
+        self.initial != 0 || self.increase
+    }
+
+    pub fn trigger(&mut self) {
+        self.volume = self.initial;
+        self.timer = self.period;
+    }
+
+    // a period of 0 stops the envelope
+    pub fn clock(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = self.period;
+            if self.increase && self.volume < 15 {
+                self.volume += 1;
+            } else if !self.increase && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
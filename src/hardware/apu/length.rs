@@ -0,0 +1,59 @@
+// silences a channel after 64 (256 for the wave channel) frame sequencer length steps
+#[derive(Copy, Clone)]
+pub struct Length {
+    max: u16,
+    counter: u16,
+    pub enabled: bool,
+}
+
+impl Length {
+    pub fn new(max: u16) -> Self {
+        Length {
+            max,
+            counter: 0,
+            enabled: false,
+        }
+    }
+
+    // turning the APU off leaves the counter alone but clears the enable bit with NRx4
+    pub fn powered_off(&self) -> Self {
+        Length {
+            enabled: false,
+            ..*self
+        }
+    }
+
+    pub fn load(&mut self, value: u8) {
+        self.counter = self.max - value as u16;
+    }
+
+    // returns true when the counter runs out and the channel turns off
+    pub fn clock(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            return self.counter == 0;
+        }
+        false
+    }
+
+    // enabling the counter while the next sequencer step doesn't clock length clocks it once
+    // right away, returns true if that runs it out
+    pub fn set_enabled(&mut self, enabled: bool, extra_clock: bool) -> bool {
+        let was_enabled = self.enabled;
+        self.enabled = enabled;
+        if !was_enabled && extra_clock {
+            return self.clock();
+        }
+        false
+    }
+
+    // a trigger with the counter at zero reloads it to the max, minus the same extra clock
+    pub fn trigger(&mut self, extra_clock: bool) {
+        if self.counter == 0 {
+            self.counter = self.max;
+            if self.enabled && extra_clock {
+                self.counter -= 1;
+            }
+        }
+    }
+}
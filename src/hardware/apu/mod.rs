@@ -0,0 +1,367 @@
+use std::ops::RangeInclusive;
+
+use crate::hardware::apu::noise::Noise;
+use crate::hardware::apu::pulse::Pulse;
+use crate::hardware::apu::wave::Wave;
+
+pub mod envelope;
+pub mod length;
+pub mod noise;
+pub mod pulse;
+pub mod wave;
+
+// T-cycles per second
+pub const CLOCK_RATE: u32 = 4_194_304;
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+
+// channel 1, the one the boot ROM plays the chime on
+pub const NR11: u16 = 0xFF11;
+pub const NR12: u16 = 0xFF12;
+pub const NR13: u16 = 0xFF13;
+pub const NR14: u16 = 0xFF14;
+pub const NR50: u16 = 0xFF24;
+pub const NR51: u16 = 0xFF25;
+pub const NR52: u16 = 0xFF26;
+pub const WAVE_RAM: RangeInclusive<u16> = 0xFF30..=0xFF3F;
+
+// NRx0 to NRx4 of each channel
+const PULSE1: RangeInclusive<u16> = 0xFF10..=0xFF14;
+const PULSE2: RangeInclusive<u16> = 0xFF15..=0xFF19;
+const WAVE: RangeInclusive<u16> = 0xFF1A..=0xFF1E;
+const NOISE: RangeInclusive<u16> = 0xFF1F..=0xFF23;
+
+// the frame sequencer steps on the falling edge of this bit of the timer's counter, bit 4 of DIV
+const FRAME_SEQUENCER_BIT: u16 = 1 << 12;
+
+pub struct APU {
+    powered: bool,
+    pulse1: Pulse,
+    pulse2: Pulse,
+    wave: Wave,
+    noise: Noise,
+    // master volume, bits 6-4 left and 2-0 right
+    nr50: u8,
+    // panning, bits 7-4 send channels 4-1 left and bits 3-0 send them right
+    nr51: u8,
+    // the next frame sequencer step, 0-7
+    frame_step: u8,
+    divider_bit: bool,
+    sample_rate: u32,
+    // T-cycles times the sample rate since the last sample
+    sample_clock: u32,
+    // high-pass filter state per side, removes the DC offset of the DACs
+    capacitor: [f32; 2],
+    charge_factor: f32,
+    // interleaved left and right samples waiting to be drained
+    samples: Vec<f32>,
+}
+
+impl APU {
+    pub fn new() -> Self {
+        let mut apu = APU {
+            // as left by the boot ROM
+            powered: true,
+            pulse1: Pulse::new(true),
+            pulse2: Pulse::new(false),
+            wave: Wave::new(),
+            noise: Noise::new(),
+            nr50: 0x77,
+            nr51: 0xF3,
+            frame_step: 0,
+            divider_bit: false,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            sample_clock: 0,
+            capacitor: [0.0; 2],
+            charge_factor: 0.0,
+            samples: Vec::new(),
+        };
+        apu.set_sample_rate(DEFAULT_SAMPLE_RATE);
+        apu
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        assert!(sample_rate > 0 && sample_rate <= CLOCK_RATE, "unsupported sample rate {}", sample_rate);
+        self.sample_rate = sample_rate;
+        self.sample_clock = 0;
+        self.charge_factor = 0.999958f32.powf(CLOCK_RATE as f32 / sample_rate as f32);
+    }
+
+    // takes the samples produced so far, interleaved left and right in -1.0..=1.0
+    pub fn drain_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+
+    pub fn is_powered(&self) -> bool {
+        self.powered
+    }
+
+    // advances one M-cycle, divider is the timer's internal counter after this cycle
+    pub fn tick(&mut self, divider: u16) {
+        let divider_bit = divider & FRAME_SEQUENCER_BIT != 0;
+        if self.powered && self.divider_bit && !divider_bit {
+            self.step_frame_sequencer();
+        }
+        self.divider_bit = divider_bit;
+
+        if self.powered {
+            for _ in 0..4 {
+                self.pulse1.tick();
+                self.pulse2.tick();
+                self.wave.tick();
+                self.noise.tick();
+            }
+        }
+
+        self.sample_clock += self.sample_rate * 4;
+        while self.sample_clock >= CLOCK_RATE {
+            self.sample_clock -= CLOCK_RATE;
+            self.push_sample();
+        }
+    }
+
+    // 512 Hz: length on even steps, sweep on 2 and 6, envelope on 7
+    fn step_frame_sequencer(&mut self) {
+        if self.frame_step & 1 == 0 {
+            self.pulse1.clock_length();
+            self.pulse2.clock_length();
+            self.wave.clock_length();
+            self.noise.clock_length();
+        }
+        if self.frame_step == 2 || self.frame_step == 6 {
+            self.pulse1.clock_sweep();
+        }
+        if self.frame_step == 7 {
+            self.pulse1.clock_envelope();
+            self.pulse2.clock_envelope();
+            self.noise.clock_envelope();
+        }
+        self.frame_step = (self.frame_step + 1) % 8;
+    }
+
+    // whether the next frame sequencer step leaves length alone
+    fn extra_length_clock(&self) -> bool {
+        self.frame_step & 1 == 1
+    }
+
+    fn push_sample(&mut self) {
+        let outputs = [
+            Self::dac(self.pulse1.is_dac_enabled(), self.pulse1.output()),
+            Self::dac(self.pulse2.is_dac_enabled(), self.pulse2.output()),
+            Self::dac(self.wave.is_dac_enabled(), self.wave.output()),
+            Self::dac(self.noise.is_dac_enabled(), self.noise.output()),
+        ];
+
+        let (mut left, mut right) = (0.0, 0.0);
+        for (channel, output) in outputs.iter().enumerate() {
+            if self.nr51 & (0x10 << channel) != 0 {
+                left += output;
+            }
+            if self.nr51 & (0x01 << channel) != 0 {
+                right += output;
+            }
+        }
+
+        let left_volume = (((self.nr50 >> 4) & 0b111) + 1) as f32 / 8.0;
+        let right_volume = ((self.nr50 & 0b111) + 1) as f32 / 8.0;
+        let left = self.high_pass(0, left / 4.0 * left_volume);
+        let right = self.high_pass(1, right / 4.0 * right_volume);
+
+        // a second of audio is plenty, nobody is draining the buffer past that
+        if self.samples.len() < self.sample_rate as usize * 2 {
+            self.samples.push(left);
+            self.samples.push(right);
+        }
+    }
+
+    // a DAC maps 0-15 to -1.0..=1.0, a disabled one outputs nothing
+    fn dac(enabled: bool, output: u8) -> f32 {
+        if enabled { output as f32 / 7.5 - 1.0 } else { 0.0 }
+    }
+
+    fn high_pass(&mut self, side: usize, input: f32) -> f32 {
+        let output = input - self.capacitor[side];
+        self.capacitor[side] = input - output * self.charge_factor;
+        output
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            _ if PULSE1.contains(&address) => self.pulse1.read(address - PULSE1.start()),
+            _ if PULSE2.contains(&address) => self.pulse2.read(address - PULSE2.start()),
+            _ if WAVE.contains(&address) => self.wave.read(address - WAVE.start()),
+            _ if NOISE.contains(&address) => self.noise.read(address - NOISE.start()),
+            NR50 => self.nr50,
+            NR51 => self.nr51,
+            NR52 => {
+                (self.powered as u8) << 7
+                    | 0x70
+                    | (self.noise.enabled as u8) << 3
+                    | (self.wave.enabled as u8) << 2
+                    | (self.pulse2.enabled as u8) << 1
+                    | self.pulse1.enabled as u8
+            }
+            _ if WAVE_RAM.contains(&address) => self.wave.read_ram((address - WAVE_RAM.start()) as usize),
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        if address == NR52 {
+            self.write_power(value);
+            return;
+        }
+        if WAVE_RAM.contains(&address) {
+            self.wave.write_ram((address - WAVE_RAM.start()) as usize, value);
+            return;
+        }
+
+        if !self.powered {
+            // with the APU off only the length counters can still be written
+            match address {
+                0xFF11 => self.pulse1.length.load(value & 0x3F),
+                0xFF16 => self.pulse2.length.load(value & 0x3F),
+                0xFF1B => self.wave.length.load(value),
+                0xFF20 => self.noise.length.load(value & 0x3F),
+                _ => {}
+            }
+            return;
+        }
+
+        let extra_length_clock = self.extra_length_clock();
+        match address {
+            _ if PULSE1.contains(&address) => self.pulse1.write(address - PULSE1.start(), value, extra_length_clock),
+            _ if PULSE2.contains(&address) => self.pulse2.write(address - PULSE2.start(), value, extra_length_clock),
+            _ if WAVE.contains(&address) => self.wave.write(address - WAVE.start(), value, extra_length_clock),
+            _ if NOISE.contains(&address) => self.noise.write(address - NOISE.start(), value, extra_length_clock),
+            NR50 => self.nr50 = value,
+            NR51 => self.nr51 = value,
+            _ => {}
+        }
+    }
+
+    fn write_power(&mut self, value: u8) {
+        let powered = value & 0x80 != 0;
+        if self.powered && !powered {
+            self.pulse1.power_off();
+            self.pulse2.power_off();
+            self.wave.power_off();
+            self.noise.power_off();
+            self.nr50 = 0;
+            self.nr51 = 0;
+        } else if !self.powered && powered {
+            self.frame_step = 0;
+        }
+        self.powered = powered;
+    }
+}
+
+impl Default for APU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::apu::{APU, CLOCK_RATE, NR50, NR51, NR52};
+
+    // steps the frame sequencer the way the timer's counter would, 8192 T-cycles per step
+    fn run_frame_sequencer(apu: &mut APU, steps: usize) {
+        let mut divider: u16 = 0;
+        for _ in 0..steps * 2048 {
+            divider = divider.wrapping_add(4);
+            apu.tick(divider);
+        }
+    }
+
+    #[test]
+    fn unused_bits_read_back_as_one() {
+        let mut apu = APU::new();
+        apu.write(NR52, 0x00);
+        apu.write(NR52, 0x80);
+        let expected = [
+            0x80, 0x3F, 0x00, 0xFF, 0xBF, 0xFF, 0x3F, 0x00, 0xFF, 0xBF, 0x7F, 0xFF, 0x9F, 0xFF, 0xBF,
+            0xFF, 0xFF, 0x00, 0x00, 0xBF, 0x00, 0x00, 0xF0,
+        ];
+        for (offset, &value) in expected.iter().enumerate() {
+            let address = 0xFF10 + offset as u16;
+            assert_eq!(apu.read(address), value, "0x{:04X}", address);
+        }
+        for address in 0xFF27..=0xFF2F {
+            assert_eq!(apu.read(address), 0xFF);
+        }
+    }
+
+    #[test]
+    fn power_off_clears_registers_and_ignores_writes() {
+        let mut apu = APU::new();
+        apu.write(0xFF12, 0xF0);
+        apu.write(0xFF30, 0x12);
+        apu.write(NR52, 0x00);
+
+        assert_eq!(apu.read(0xFF12), 0x00);
+        assert_eq!(apu.read(NR50), 0x00);
+        apu.write(NR51, 0xFF);
+        assert_eq!(apu.read(NR51), 0x00);
+        // wave RAM is left alone
+        assert_eq!(apu.read(0xFF30), 0x12);
+        assert_eq!(apu.read(NR52), 0x70);
+    }
+
+    #[test]
+    fn trigger_enables_channel_until_length_runs_out() {
+        let mut apu = APU::new();
+        // channel 2: DAC on, length of 2, trigger with length enabled
+        apu.write(0xFF17, 0xF0);
+        apu.write(0xFF16, 62);
+        apu.write(0xFF19, 0xC0);
+        assert_eq!(apu.read(NR52) & 0b10, 0b10);
+
+        // steps 0 and 2 clock length
+        run_frame_sequencer(&mut apu, 2);
+        assert_eq!(apu.read(NR52) & 0b10, 0b10);
+        run_frame_sequencer(&mut apu, 1);
+        assert_eq!(apu.read(NR52) & 0b10, 0);
+    }
+
+    #[test]
+    fn dac_off_disables_channel() {
+        let mut apu = APU::new();
+        apu.write(0xFF1A, 0x80);
+        apu.write(0xFF1E, 0x80);
+        assert_eq!(apu.read(NR52) & 0b100, 0b100);
+        apu.write(0xFF1A, 0x00);
+        assert_eq!(apu.read(NR52) & 0b100, 0);
+    }
+
+    #[test]
+    fn sweep_overflow_disables_channel() {
+        let mut apu = APU::new();
+        // period 1, increasing, shift 1, from a frequency of 0x500 the second step overflows
+        apu.write(0xFF10, 0x11);
+        apu.write(0xFF12, 0xF0);
+        apu.write(0xFF13, 0x00);
+        apu.write(0xFF14, 0x85);
+        assert_eq!(apu.read(NR52) & 0b1, 0b1);
+
+        run_frame_sequencer(&mut apu, 4);
+        assert_eq!(apu.read(NR52) & 0b1, 0);
+    }
+
+    #[test]
+    fn produces_stereo_samples_at_the_sample_rate() {
+        let mut apu = APU::new();
+        apu.set_sample_rate(32_768);
+        // a second is 2^20 M-cycles
+        for _ in 0..CLOCK_RATE / 4 {
+            apu.tick(0);
+        }
+        let samples = apu.drain_samples();
+        assert_eq!(samples.len(), 2 * 32_768);
+        assert!(apu.drain_samples().is_empty());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::hardware::apu::envelope::Envelope;
+use crate::hardware::apu::length::Length;
+
+const DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+// channel 4, the output is the inverted low bit of a linear feedback shift register
+pub struct Noise {
+    pub enabled: bool,
+    // NR43: clock shift in bits 7-4, 7-bit mode in bit 3, divisor code in bits 2-0
+    shift: u8,
+    short_mode: bool,
+    divisor: u8,
+    timer: u32,
+    lfsr: u16,
+    pub length: Length,
+    pub envelope: Envelope,
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Noise {
+            enabled: false,
+            shift: 0,
+            short_mode: false,
+            divisor: 0,
+            timer: 0,
+            lfsr: 0x7FFF,
+            length: Length::new(64),
+            envelope: Envelope::new(),
+        }
+    }
+
+    pub fn power_off(&mut self) {
+        *self = Noise {
+            length: self.length.powered_off(),
+            ..Noise::new()
+        };
+    }
+
+    fn period(&self) -> u32 {
+        DIVISORS[self.divisor as usize] << self.shift
+    }
+
+    // register is the offset from the unused 0xFF1F, so NR41 is 1
+    pub fn read(&self, register: u16) -> u8 {
+        match register {
+            0 | 1 => 0xFF,
+            2 => self.envelope.read(),
+            3 => self.shift << 4 | (self.short_mode as u8) << 3 | self.divisor,
+            _ => 0xBF | (self.length.enabled as u8) << 6,
+        }
+    }
+
+    pub fn write(&mut self, register: u16, value: u8, extra_length_clock: bool) {
+        match register {
+            0 => {}
+            1 => self.length.load(value & 0x3F),
+            2 => {
+                self.envelope.write(value);
+                if !self.envelope.is_dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => {
+                self.shift = value >> 4;
+                self.short_mode = value & 0b1000 != 0;
+                self.divisor = value & 0b111;
+            }
+            _ => {
+                if self.length.set_enabled(value & 0x40 != 0, extra_length_clock) {
+                    self.enabled = false;
+                }
+                if value & 0x80 != 0 {
+                    self.enabled = self.envelope.is_dac_enabled();
+                    self.length.trigger(extra_length_clock);
+                    self.envelope.trigger();
+                    self.timer = self.period();
+                    self.lfsr = 0x7FFF;
+                }
+            }
+        }
+    }
+
+    // advances one T-cycle
+    pub fn tick(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = self.period();
+            let feedback = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+            self.lfsr = (self.lfsr >> 1) | (feedback << 14);
+            if self.short_mode {
+                self.lfsr = (self.lfsr & !(1 << 6)) | (feedback << 6);
+            }
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn is_dac_enabled(&self) -> bool {
+        self.envelope.is_dac_enabled()
+    }
+
+    // the digital output, 0-15
+    pub fn output(&self) -> u8 {
+        if self.enabled && self.lfsr & 1 == 0 { self.envelope.volume } else { 0 }
+    }
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
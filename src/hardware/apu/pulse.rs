@@ -0,0 +1,206 @@
+use crate::hardware::apu::envelope::Envelope;
+use crate::hardware::apu::length::Length;
+
+// one bit per step of the waveform, 12.5%, 25%, 50% and 75% high
+const DUTY_PATTERNS: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+
+// NR10, channel 1 only: period in bits 6-4, direction in bit 3, shift in bits 2-0
+struct Sweep {
+    period: u8,
+    negate: bool,
+    shift: u8,
+    timer: u8,
+    enabled: bool,
+    shadow: u16,
+    // a subtraction happened since the last trigger, clearing negate now turns the channel off
+    negated: bool,
+}
+
+impl Sweep {
+    fn new() -> Self {
+        Sweep {
+            period: 0,
+            negate: false,
+            shift: 0,
+            timer: 0,
+            enabled: false,
+            shadow: 0,
+            negated: false,
+        }
+    }
+
+    fn reload_timer(&mut self) {
+        // a period of 0 is treated as 8
+        self.timer = if self.period == 0 { 8 } else { self.period };
+    }
+
+    // the next frequency, or None when it overflows 11 bits
+    fn calculate(&mut self) -> Option<u16> {
+        let delta = self.shadow >> self.shift;
+        let frequency = if self.negate {
+            self.negated = true;
+            self.shadow - delta
+        } else {
+            self.shadow + delta
+        };
+        if frequency > 0x7FF { None } else { Some(frequency) }
+    }
+}
+
+// channels 1 and 2, channel 2 is the same without the sweep unit
+pub struct Pulse {
+    pub enabled: bool,
+    sweep: Option<Sweep>,
+    duty: u8,
+    duty_step: u8,
+    frequency: u16,
+    timer: u16,
+    pub length: Length,
+    pub envelope: Envelope,
+}
+
+impl Pulse {
+    pub fn new(with_sweep: bool) -> Self {
+        Pulse {
+            enabled: false,
+            sweep: if with_sweep { Some(Sweep::new()) } else { None },
+            duty: 0,
+            duty_step: 0,
+            frequency: 0,
+            timer: 0,
+            length: Length::new(64),
+            envelope: Envelope::new(),
+        }
+    }
+
+    // everything but the length counter is cleared with the APU off
+    pub fn power_off(&mut self) {
+        *self = Pulse {
+            length: self.length.powered_off(),
+            ..Pulse::new(self.sweep.is_some())
+        };
+    }
+
+    // register is the offset from NRx0
+    pub fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => match &self.sweep {
+                Some(sweep) => 0x80 | sweep.period << 4 | (sweep.negate as u8) << 3 | sweep.shift,
+                None => 0xFF,
+            },
+            1 => self.duty << 6 | 0x3F,
+            2 => self.envelope.read(),
+            3 => 0xFF,
+            _ => 0xBF | (self.length.enabled as u8) << 6,
+        }
+    }
+
+    pub fn write(&mut self, register: u16, value: u8, extra_length_clock: bool) {
+        match register {
+            0 => {
+                if let Some(sweep) = &mut self.sweep {
+                    let negate = value & 0b1000 != 0;
+                    if sweep.negated && sweep.negate && !negate {
+                        self.enabled = false;
+                    }
+                    sweep.period = (value >> 4) & 0b111;
+                    sweep.negate = negate;
+                    sweep.shift = value & 0b111;
+                }
+            }
+            1 => {
+                self.duty = value >> 6;
+                self.length.load(value & 0x3F);
+            }
+            2 => {
+                self.envelope.write(value);
+                if !self.envelope.is_dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.frequency = (self.frequency & 0x700) | value as u16,
+            _ => {
+                self.frequency = (self.frequency & 0xFF) | ((value as u16 & 0b111) << 8);
+                if self.length.set_enabled(value & 0x40 != 0, extra_length_clock) {
+                    self.enabled = false;
+                }
+                if value & 0x80 != 0 {
+                    self.trigger(extra_length_clock);
+                }
+            }
+        }
+    }
+
+    fn trigger(&mut self, extra_length_clock: bool) {
+        self.enabled = self.envelope.is_dac_enabled();
+        self.length.trigger(extra_length_clock);
+        self.envelope.trigger();
+        self.timer = (2048 - self.frequency) * 4;
+
+        if let Some(sweep) = &mut self.sweep {
+            sweep.shadow = self.frequency;
+            sweep.reload_timer();
+            sweep.negated = false;
+            sweep.enabled = sweep.period != 0 || sweep.shift != 0;
+            if sweep.shift != 0 && sweep.calculate().is_none() {
+                self.enabled = false;
+            }
+        }
+    }
+
+    // advances one T-cycle
+    pub fn tick(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = (2048 - self.frequency) * 4;
+            self.duty_step = (self.duty_step + 1) % 8;
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_sweep(&mut self) {
+        let Some(sweep) = &mut self.sweep else { return };
+        sweep.timer = sweep.timer.saturating_sub(1);
+        if sweep.timer > 0 {
+            return;
+        }
+        sweep.reload_timer();
+        if !sweep.enabled || sweep.period == 0 {
+            return;
+        }
+
+        match sweep.calculate() {
+            None => self.enabled = false,
+            Some(frequency) if sweep.shift != 0 => {
+                sweep.shadow = frequency;
+                self.frequency = frequency;
+                // the new frequency is checked for overflow again straight away
+                if sweep.calculate().is_none() {
+                    self.enabled = false;
+                }
+            }
+            Some(_) => {}
+        }
+    }
+
+    pub fn is_dac_enabled(&self) -> bool {
+        self.envelope.is_dac_enabled()
+    }
+
+    // the digital output, 0-15
+    pub fn output(&self) -> u8 {
+        let high = (DUTY_PATTERNS[self.duty as usize] >> (7 - self.duty_step)) & 1 == 1;
+        if self.enabled && high { self.envelope.volume } else { 0 }
+    }
+}
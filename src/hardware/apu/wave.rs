@@ -0,0 +1,136 @@
+use crate::hardware::apu::length::Length;
+
+pub const WAVE_RAM_SIZE: usize = 0x10;
+
+// channel 3, plays back the 32 4-bit samples in wave RAM
+pub struct Wave {
+    pub enabled: bool,
+    dac_enabled: bool,
+    // NR32 bits 6-5: mute, 100%, 50%, 25%
+    volume: u8,
+    frequency: u16,
+    timer: u16,
+    position: u8,
+    // the last sample read from wave RAM, it is what a trigger starts out playing
+    sample: u8,
+    pub length: Length,
+    pub ram: [u8; WAVE_RAM_SIZE],
+}
+
+impl Wave {
+    pub fn new() -> Self {
+        Wave {
+            enabled: false,
+            dac_enabled: false,
+            volume: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            sample: 0,
+            length: Length::new(256),
+            ram: [0; WAVE_RAM_SIZE],
+        }
+    }
+
+    // wave RAM and the length counter survive the APU being turned off
+    pub fn power_off(&mut self) {
+        *self = Wave {
+            length: self.length.powered_off(),
+            ram: self.ram,
+            ..Wave::new()
+        };
+    }
+
+    // register is the offset from NR30
+    pub fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => (self.dac_enabled as u8) << 7 | 0x7F,
+            1 => 0xFF,
+            2 => self.volume << 5 | 0x9F,
+            3 => 0xFF,
+            _ => 0xBF | (self.length.enabled as u8) << 6,
+        }
+    }
+
+    pub fn write(&mut self, register: u16, value: u8, extra_length_clock: bool) {
+        match register {
+            0 => {
+                self.dac_enabled = value & 0x80 != 0;
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            }
+            1 => self.length.load(value),
+            2 => self.volume = (value >> 5) & 0b11,
+            3 => self.frequency = (self.frequency & 0x700) | value as u16,
+            _ => {
+                self.frequency = (self.frequency & 0xFF) | ((value as u16 & 0b111) << 8);
+                if self.length.set_enabled(value & 0x40 != 0, extra_length_clock) {
+                    self.enabled = false;
+                }
+                if value & 0x80 != 0 {
+                    self.enabled = self.dac_enabled;
+                    self.length.trigger(extra_length_clock);
+                    self.timer = (2048 - self.frequency) * 2;
+                    self.position = 0;
+                }
+            }
+        }
+    }
+
+    // while the channel plays, wave RAM accesses go to the byte being played
+    pub fn read_ram(&self, offset: usize) -> u8 {
+        if self.enabled { self.ram[self.position as usize / 2] } else { self.ram[offset] }
+    }
+
+    pub fn write_ram(&mut self, offset: usize, value: u8) {
+        if self.enabled {
+            self.ram[self.position as usize / 2] = value;
+        } else {
+            self.ram[offset] = value;
+        }
+    }
+
+    // advances one T-cycle
+    pub fn tick(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = (2048 - self.frequency) * 2;
+            self.position = (self.position + 1) % 32;
+            let byte = self.ram[self.position as usize / 2];
+            // the upper nibble is played first
+            self.sample = if self.position & 1 == 0 { byte >> 4 } else { byte & 0x0F };
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn is_dac_enabled(&self) -> bool {
+        self.dac_enabled
+    }
+
+    // the digital output, 0-15
+    pub fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        match self.volume {
+            0 => 0,
+            1 => self.sample,
+            2 => self.sample >> 1,
+            _ => self.sample >> 2,
+        }
+    }
+}
+
+impl Default for Wave {
+    fn default() -> Self {
+        Self::new()
+    }
+}
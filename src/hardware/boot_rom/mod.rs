@@ -0,0 +1,104 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::hardware::model::Model;
+
+pub mod replacement;
+
+// writing a value with bit 0 set unmaps the boot ROM until the next reset
+pub const BOOT: u16 = 0xFF50;
+
+pub const DMG_BOOT_ROM_SIZE: usize = 0x100;
+pub const CGB_BOOT_ROM_SIZE: usize = 0x900;
+
+const LOWER: RangeInclusive<u16> = 0x0000..=0x00FF;
+// the CGB boot ROM is split around the cartridge header
+const UPPER: RangeInclusive<u16> = 0x0200..=0x08FF;
+
+#[derive(Debug)]
+pub enum BootROMError {
+    WrongSize { model: Model, expected: usize, actual: usize },
+}
+
+impl fmt::Display for BootROMError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BootROMError::WrongSize { model, expected, actual } => {
+                write!(f, "a {:?} boot ROM is {} bytes, not {}", model, expected, actual)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BootROMError {}
+
+// mapped over the start of the cartridge ROM while it runs
+pub struct BootROM {
+    data: Vec<u8>,
+}
+
+impl BootROM {
+    pub fn new(model: Model, data: Vec<u8>) -> Result<Self, BootROMError> {
+        let expected = if model.is_cgb() { CGB_BOOT_ROM_SIZE } else { DMG_BOOT_ROM_SIZE };
+        if data.len() != expected {
+            return Err(BootROMError::WrongSize { model, expected, actual: data.len() });
+        }
+        Ok(BootROM { data })
+    }
+
+    // whether reads from address go to the boot ROM instead of the cartridge
+    pub fn maps(&self, address: u16) -> bool {
+        LOWER.contains(&address) || (self.data.len() == CGB_BOOT_ROM_SIZE && UPPER.contains(&address))
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        self.data[address as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::boot_rom::{BOOT, BootROM, BootROMError, CGB_BOOT_ROM_SIZE};
+    use crate::hardware::cartridge::Cartridge;
+    use crate::hardware::cpu::bus::SystemBus;
+    use crate::hardware::cpu::CPU;
+    use crate::hardware::model::Model;
+
+    #[test]
+    fn checks_the_size_for_the_model() {
+        assert!(BootROM::new(Model::DMG, vec![0; 0x100]).is_ok());
+        assert!(matches!(
+            BootROM::new(Model::CGB, vec![0; 0x100]),
+            Err(BootROMError::WrongSize { expected: CGB_BOOT_ROM_SIZE, actual: 0x100, .. })
+        ));
+    }
+
+    #[test]
+    fn cgb_boot_rom_leaves_the_header_visible() {
+        let boot_rom = BootROM::new(Model::CGB, vec![0; CGB_BOOT_ROM_SIZE]).unwrap();
+        assert!(boot_rom.maps(0x00FF));
+        assert!(!boot_rom.maps(0x0100));
+        assert!(!boot_rom.maps(0x014F));
+        assert!(boot_rom.maps(0x0200));
+        assert!(boot_rom.maps(0x08FF));
+        assert!(!boot_rom.maps(0x0900));
+    }
+
+    #[test]
+    fn runs_from_zero_until_unmapped() {
+        // LD A, 0x01; LDH (0x50), A
+        let mut data = vec![0; 0x100];
+        data[..4].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+        let boot_rom = BootROM::new(Model::DMG, data).unwrap();
+        let mut bus = SystemBus::with_boot_rom(Cartridge::with_program(0x0000, &[0xAA]), boot_rom);
+        let mut cpu = CPU::new();
+        cpu.registers.pc = 0x0000;
+        assert_eq!(bus.read(0x0000), 0x3E);
+
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert!(bus.boot_rom.is_none());
+        assert_eq!(bus.read(0x0000), 0xAA);
+        assert_eq!(bus.read(BOOT), 0xFF);
+    }
+}
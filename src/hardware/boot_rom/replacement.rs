@@ -0,0 +1,203 @@
+// A boot program written for this emulator, under the same MIT/Apache-2.0 terms as the rest of it,
+// for when there is no dump of Nintendo's. It scrolls the logo from the cartridge header down the
+// screen, plays the chime, and hands over with the registers the model's own boot ROM leaves. What
+// those are depends on the cartridge, so the image is built for it and the values patched in.
+// CGB models get the same program in a CGB-sized image, DMG games aren't colorized since there is
+// no CGB palette RAM for it to set up.
+
+use crate::hardware::boot_rom::{BootROM, CGB_BOOT_ROM_SIZE, DMG_BOOT_ROM_SIZE};
+use crate::hardware::cartridge::header::CartridgeHeader;
+use crate::hardware::model::Model;
+
+// the handoff pops AF, BC, DE and HL from here
+const REGISTERS: usize = 0x00EC;
+
+const PROGRAM: [u8; 0xA3] = [
+    0x31, 0xFE, 0xFF, // 0000 LD SP, $FFFE
+    0xAF, //             0003 XOR A
+    0x21, 0xFF, 0x9F, // 0004 LD HL, $9FFF
+    0x32, //             0007 .clear_vram: LD (HL-), A
+    0xCB, 0x7C, //       0008 BIT 7, H
+    0x20, 0xFB, //       000A JR NZ, .clear_vram
+    // sound on, channel 1 ready for the chime
+    0x3E, 0x80, //       000C LD A, $80
+    0xE0, 0x26, //       000E LDH (NR52), A
+    0xE0, 0x11, //       0010 LDH (NR11), A
+    0x3E, 0xF3, //       0012 LD A, $F3
+    0xE0, 0x12, //       0014 LDH (NR12), A
+    0xE0, 0x25, //       0016 LDH (NR51), A
+    0x3E, 0x77, //       0018 LD A, $77
+    0xE0, 0x24, //       001A LDH (NR50), A
+    0x3E, 0xFC, //       001C LD A, $FC
+    0xE0, 0x47, //       001E LDH (BGP), A
+    // every nibble of the logo becomes two rows of tile 1-24, twice as wide
+    0x11, 0x04, 0x01, // 0020 LD DE, $0104
+    0x21, 0x10, 0x80, // 0023 LD HL, $8010
+    0x1A, //             0026 .logo: LD A, (DE)
+    0xCD, 0x88, 0x00, // 0027 CALL .double_upper
+    0xCD, 0x89, 0x00, // 002A CALL .double_lower
+    0x13, //             002D INC DE
+    0x7B, //             002E LD A, E
+    0xFE, 0x34, //       002F CP $34
+    0x20, 0xF3, //       0031 JR NZ, .logo
+    // the (R) mark as tile 25
+    0x11, 0x9B, 0x00, // 0033 LD DE, .registered
+    0x06, 0x08, //       0036 LD B, 8
+    0x1A, //             0038 .mark: LD A, (DE)
+    0x13, //             0039 INC DE
+    0x22, //             003A LD (HL+), A
+    0x23, //             003B INC HL
+    0x05, //             003C DEC B
+    0x20, 0xF9, //       003D JR NZ, .mark
+    // tiles 1-12 from 0x9904 and 13-24 from 0x9924, filled backwards
+    0x3E, 0x19, //       003F LD A, $19
+    0xEA, 0x10, 0x99, // 0041 LD ($9910), A
+    0x21, 0x2F, 0x99, // 0044 LD HL, $992F
+    0x0E, 0x0C, //       0047 .row: LD C, 12
+    0x3D, //             0049 .tile: DEC A
+    0x28, 0x08, //       004A JR Z, .scroll
+    0x32, //             004C LD (HL-), A
+    0x0D, //             004D DEC C
+    0x20, 0xF9, //       004E JR NZ, .tile
+    0x2E, 0x0F, //       0050 LD L, $0F
+    0x18, 0xF3, //       0052 JR .row
+    // one line per frame from 100 lines down
+    0x3E, 0x64, //       0054 .scroll: LD A, $64
+    0xE0, 0x42, //       0056 LDH (SCY), A
+    0x3E, 0x91, //       0058 LD A, $91
+    0xE0, 0x40, //       005A LDH (LCDC), A
+    0xCD, 0x7B, 0x00, // 005C .scroll_frame: CALL .wait_frame
+    0xF0, 0x42, //       005F LDH A, (SCY)
+    0x3D, //             0061 DEC A
+    0xE0, 0x42, //       0062 LDH (SCY), A
+    0x20, 0xF6, //       0064 JR NZ, .scroll_frame
+    0x3E, 0xC1, //       0066 LD A, $C1
+    0xE0, 0x13, //       0068 LDH (NR13), A
+    0x3E, 0x87, //       006A LD A, $87
+    0xE0, 0x14, //       006C LDH (NR14), A
+    0x06, 0x30, //       006E LD B, 48
+    0xCD, 0x7B, 0x00, // 0070 .hold: CALL .wait_frame
+    0x05, //             0073 DEC B
+    0x20, 0xFA, //       0074 JR NZ, .hold
+    0x00, 0x00, 0x00, // 0076 NOP, NOP, NOP, room for a CGB stage
+    0x18, 0x79, //       0079 JR .handoff
+    // returns at the start of the next VBlank
+    0xF0, 0x44, //       007B .wait_frame: LDH A, (LY)
+    0xFE, 0x90, //       007D CP 144
+    0x28, 0xFA, //       007F JR Z, .wait_frame
+    0xF0, 0x44, //       0081 .wait_vblank: LDH A, (LY)
+    0xFE, 0x90, //       0083 CP 144
+    0x20, 0xFA, //       0085 JR NZ, .wait_vblank
+    0xC9, //             0087 RET
+    // writes the 4 bits from the top of C as 8 doubled pixels, to two rows of bitplane 0
+    0x4F, //             0088 .double_upper: LD C, A
+    0x06, 0x04, //       0089 .double_lower: LD B, 4
+    0xC5, //             008B .bit: PUSH BC
+    0xCB, 0x11, //       008C RL C
+    0x17, //             008E RLA
+    0xC1, //             008F POP BC
+    0xCB, 0x11, //       0090 RL C
+    0x17, //             0092 RLA
+    0x05, //             0093 DEC B
+    0x20, 0xF5, //       0094 JR NZ, .bit
+    0x22, //             0096 LD (HL+), A
+    0x23, //             0097 INC HL
+    0x22, //             0098 LD (HL+), A
+    0x23, //             0099 INC HL
+    0xC9, //             009A RET
+    // 009B .registered
+    0x3C, 0x42, 0xB9, 0xA5, 0xB9, 0xA5, 0x42, 0x3C,
+];
+
+const HANDOFF: [u8; 12] = [
+    0x31, 0xEC, 0x00, // 00F4 .handoff: LD SP, REGISTERS
+    0xF1, //             00F7 POP AF
+    0xC1, //             00F8 POP BC
+    0xD1, //             00F9 POP DE
+    0xE1, //             00FA POP HL
+    0x31, 0xFE, 0xFF, // 00FB LD SP, $FFFE
+    // A is odd for every model, which is what unmaps the boot ROM
+    0xE0, 0x50, //       00FE LDH (BOOT), A
+];
+
+impl BootROM {
+    // the bundled boot program, set up to hand over like the model's boot ROM would for this cartridge
+    pub fn replacement(model: Model, header: &CartridgeHeader) -> Self {
+        let mut data = vec![0; if model.is_cgb() { CGB_BOOT_ROM_SIZE } else { DMG_BOOT_ROM_SIZE }];
+        data[..PROGRAM.len()].copy_from_slice(&PROGRAM);
+        data[DMG_BOOT_ROM_SIZE - HANDOFF.len()..DMG_BOOT_ROM_SIZE].copy_from_slice(&HANDOFF);
+
+        let registers = model.registers(header);
+        // popped low byte first, F before A
+        for (index, value) in [registers.get_af(), registers.get_bc(), registers.get_de(), registers.get_hl()].iter().enumerate() {
+            data[REGISTERS + index * 2..REGISTERS + index * 2 + 2].copy_from_slice(&value.to_le_bytes());
+        }
+        BootROM::new(model, data).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::boot_rom::BootROM;
+    use crate::hardware::cartridge::Cartridge;
+    use crate::hardware::cartridge::header::NINTENDO_LOGO;
+    use crate::hardware::gameboy::GameBoy;
+    use crate::hardware::model::Model;
+    use crate::hardware::ppu::{BGP, LCDC, SCY};
+
+    // runs the bundled boot ROM until it unmaps itself, a few seconds of emulated time at most
+    fn boot(model: Model, patch: &[(usize, u8)]) -> GameBoy {
+        let mut data = vec![0; 0x8000];
+        data[0x104..0x134].copy_from_slice(&NINTENDO_LOGO);
+        for &(address, value) in patch {
+            data[address] = value;
+        }
+        let cartridge = Cartridge::with_type(0x00, 0x00, data);
+        let boot_rom = BootROM::replacement(model, &cartridge.header);
+        let mut gameboy = GameBoy::with_boot_rom(cartridge, boot_rom);
+        for _ in 0..4_000_000 {
+            if gameboy.bus().boot_rom.is_none() {
+                return gameboy;
+            }
+            gameboy.step_instruction();
+        }
+        panic!("the boot ROM never handed over, stuck at 0x{:04X}", gameboy.cpu().registers.pc);
+    }
+
+    #[test]
+    fn hands_over_in_post_boot_state() {
+        let gameboy = boot(Model::DMG, &[(0x14D, 0xE7)]);
+        let expected = Model::DMG.registers(&gameboy.bus().cartridge.header);
+        assert_eq!(gameboy.cpu().registers.pc, 0x0100);
+        assert_eq!(gameboy.cpu().registers.sp, 0xFFFE);
+        assert_eq!(gameboy.cpu().registers.get_af(), expected.get_af());
+        assert_eq!(gameboy.cpu().registers.get_bc(), expected.get_bc());
+        assert_eq!(gameboy.cpu().registers.get_de(), expected.get_de());
+        assert_eq!(gameboy.cpu().registers.get_hl(), expected.get_hl());
+        assert_eq!(gameboy.bus().read(LCDC), 0x91);
+        assert_eq!(gameboy.bus().read(BGP), 0xFC);
+        assert_eq!(gameboy.bus().read(SCY), 0x00);
+    }
+
+    #[test]
+    fn draws_the_logo_from_the_header() {
+        let gameboy = boot(Model::DMG, &[]);
+        assert_eq!(gameboy.bus().read(0x9904), 0x01);
+        assert_eq!(gameboy.bus().read(0x990F), 0x0C);
+        assert_eq!(gameboy.bus().read(0x9910), 0x19);
+        assert_eq!(gameboy.bus().read(0x9924), 0x0D);
+        assert_eq!(gameboy.bus().read(0x992F), 0x18);
+        // 0xCE, the top left of the N, doubled
+        assert_eq!(gameboy.bus().read(0x8010), 0xF0);
+        assert_eq!(gameboy.bus().read(0x8012), 0xF0);
+        assert_eq!(gameboy.bus().read(0x8014), 0xFC);
+    }
+
+    #[test]
+    fn cgb_hands_over_with_the_title_checksum() {
+        let gameboy = boot(Model::CGB, &[(0x134, 0x20), (0x135, 0x23), (0x14B, 0x01)]);
+        assert_eq!(gameboy.cpu().registers.a, 0x11);
+        assert_eq!(gameboy.cpu().registers.get_bc(), 0x4300);
+        assert_eq!(gameboy.cpu().registers.get_hl(), 0x991A);
+    }
+}
@@ -0,0 +1,457 @@
+use std::fmt;
+
+use crate::hardware::cartridge::{Cartridge, CartridgeSection};
+use crate::hardware::cartridge::licensee::{new_licensee_name, old_licensee_name};
+
+// 0x0100-0x014F
+pub const HEADER_START: usize = 0x100;
+pub const HEADER_END: usize = 0x150;
+
+// the boot ROM refuses to start a cartridge that doesn't carry this at 0x0104
+pub const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+// the chip that does the banking
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Controller {
+    None,
+    MBC1,
+    MBC2,
+    MMM01,
+    MBC3,
+    MBC5,
+    MBC6,
+    MBC7,
+    PocketCamera,
+    TAMA5,
+    HuC3,
+    HuC1,
+    Unknown,
+}
+
+// the cartridge type byte at 0x147
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CartridgeType {
+    ROMOnly,
+    MBC1,
+    MBC1RAM,
+    MBC1RAMBattery,
+    MBC2,
+    MBC2Battery,
+    ROMRAM,
+    ROMRAMBattery,
+    MMM01,
+    MMM01RAM,
+    MMM01RAMBattery,
+    MBC3TimerBattery,
+    MBC3TimerRAMBattery,
+    MBC3,
+    MBC3RAM,
+    MBC3RAMBattery,
+    MBC5,
+    MBC5RAM,
+    MBC5RAMBattery,
+    MBC5Rumble,
+    MBC5RumbleRAM,
+    MBC5RumbleRAMBattery,
+    MBC6,
+    MBC7SensorRumbleRAMBattery,
+    PocketCamera,
+    TAMA5,
+    HuC3,
+    HuC1RAMBattery,
+    Unknown(u8),
+}
+
+impl CartridgeType {
+    pub fn from_byte(byte: u8) -> Self {
+        match byte {
+            0x00 => CartridgeType::ROMOnly,
+            0x01 => CartridgeType::MBC1,
+            0x02 => CartridgeType::MBC1RAM,
+            0x03 => CartridgeType::MBC1RAMBattery,
+            0x05 => CartridgeType::MBC2,
+            0x06 => CartridgeType::MBC2Battery,
+            0x08 => CartridgeType::ROMRAM,
+            0x09 => CartridgeType::ROMRAMBattery,
+            0x0B => CartridgeType::MMM01,
+            0x0C => CartridgeType::MMM01RAM,
+            0x0D => CartridgeType::MMM01RAMBattery,
+            0x0F => CartridgeType::MBC3TimerBattery,
+            0x10 => CartridgeType::MBC3TimerRAMBattery,
+            0x11 => CartridgeType::MBC3,
+            0x12 => CartridgeType::MBC3RAM,
+            0x13 => CartridgeType::MBC3RAMBattery,
+            0x19 => CartridgeType::MBC5,
+            0x1A => CartridgeType::MBC5RAM,
+            0x1B => CartridgeType::MBC5RAMBattery,
+            0x1C => CartridgeType::MBC5Rumble,
+            0x1D => CartridgeType::MBC5RumbleRAM,
+            0x1E => CartridgeType::MBC5RumbleRAMBattery,
+            0x20 => CartridgeType::MBC6,
+            0x22 => CartridgeType::MBC7SensorRumbleRAMBattery,
+            0xFC => CartridgeType::PocketCamera,
+            0xFD => CartridgeType::TAMA5,
+            0xFE => CartridgeType::HuC3,
+            0xFF => CartridgeType::HuC1RAMBattery,
+            _ => CartridgeType::Unknown(byte),
+        }
+    }
+
+    pub fn controller(self) -> Controller {
+        use CartridgeType::*;
+        match self {
+            ROMOnly | ROMRAM | ROMRAMBattery => Controller::None,
+            MBC1 | MBC1RAM | MBC1RAMBattery => Controller::MBC1,
+            MBC2 | MBC2Battery => Controller::MBC2,
+            MMM01 | MMM01RAM | MMM01RAMBattery => Controller::MMM01,
+            MBC3TimerBattery | MBC3TimerRAMBattery | MBC3 | MBC3RAM | MBC3RAMBattery => Controller::MBC3,
+            MBC5 | MBC5RAM | MBC5RAMBattery | MBC5Rumble | MBC5RumbleRAM | MBC5RumbleRAMBattery => Controller::MBC5,
+            MBC6 => Controller::MBC6,
+            MBC7SensorRumbleRAMBattery => Controller::MBC7,
+            PocketCamera => Controller::PocketCamera,
+            TAMA5 => Controller::TAMA5,
+            HuC3 => Controller::HuC3,
+            HuC1RAMBattery => Controller::HuC1,
+            Unknown(_) => Controller::Unknown,
+        }
+    }
+
+    // external RAM, MBC2's built-in RAM counts too
+    pub fn has_ram(self) -> bool {
+        use CartridgeType::*;
+        matches!(
+            self,
+            MBC1RAM | MBC1RAMBattery | MBC2 | MBC2Battery | ROMRAM | ROMRAMBattery | MMM01RAM | MMM01RAMBattery
+                | MBC3TimerRAMBattery | MBC3RAM | MBC3RAMBattery | MBC5RAM | MBC5RAMBattery | MBC5RumbleRAM
+                | MBC5RumbleRAMBattery | MBC7SensorRumbleRAMBattery | PocketCamera | HuC3 | HuC1RAMBattery
+        )
+    }
+
+    pub fn has_battery(self) -> bool {
+        use CartridgeType::*;
+        matches!(
+            self,
+            MBC1RAMBattery | MBC2Battery | ROMRAMBattery | MMM01RAMBattery | MBC3TimerBattery
+                | MBC3TimerRAMBattery | MBC3RAMBattery | MBC5RAMBattery | MBC5RumbleRAMBattery
+                | MBC7SensorRumbleRAMBattery | HuC1RAMBattery
+        )
+    }
+
+    pub fn has_timer(self) -> bool {
+        matches!(self, CartridgeType::MBC3TimerBattery | CartridgeType::MBC3TimerRAMBattery)
+    }
+
+    pub fn has_rumble(self) -> bool {
+        use CartridgeType::*;
+        matches!(self, MBC5Rumble | MBC5RumbleRAM | MBC5RumbleRAMBattery | MBC7SensorRumbleRAMBattery)
+    }
+}
+
+// the CGB flag at 0x143
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CGBSupport {
+    Unsupported,
+    // runs on both, with color on a CGB
+    Enhanced,
+    Exclusive,
+}
+
+// the destination code at 0x14A
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Destination {
+    Japan,
+    Overseas,
+    Unknown(u8),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Checksum<T> {
+    // what the header says
+    pub expected: T,
+    // what the ROM adds up to
+    pub actual: T,
+}
+
+impl<T: PartialEq> Checksum<T> {
+    pub fn is_valid(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+// what validate found, only the header checksum and the logo stop a real boot ROM
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub header_checksum: Checksum<u8>,
+    pub global_checksum: Checksum<u16>,
+    pub logo_valid: bool,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.header_checksum.is_valid() && self.global_checksum.is_valid() && self.logo_valid
+    }
+
+    // whether a real console would start the cartridge
+    pub fn boots(&self) -> bool {
+        self.header_checksum.is_valid() && self.logo_valid
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = |valid: bool| if valid { "ok" } else { "bad" };
+        write!(
+            f,
+            "header checksum {:02X}/{:02X} {}, global checksum {:04X}/{:04X} {}, logo {}",
+            self.header_checksum.expected,
+            self.header_checksum.actual,
+            status(self.header_checksum.is_valid()),
+            self.global_checksum.expected,
+            self.global_checksum.actual,
+            status(self.global_checksum.is_valid()),
+            status(self.logo_valid),
+        )
+    }
+}
+
+// 0x0100-0x014F as found in the ROM, decoded on access
+#[derive(Debug, Clone)]
+pub struct CartridgeHeader {
+    bytes: [u8; HEADER_END - HEADER_START],
+}
+
+impl CartridgeHeader {
+    // None when the ROM is too short to hold a header
+    pub fn from_rom(rom: &[u8]) -> Option<Self> {
+        let bytes = rom.get(HEADER_START..HEADER_END)?.try_into().ok()?;
+        Some(CartridgeHeader { bytes })
+    }
+
+    fn section(&self, section: CartridgeSection) -> &[u8] {
+        let range = Cartridge::range_map(section);
+        &self.bytes[range.start() - HEADER_START..=range.end() - HEADER_START]
+    }
+
+    fn byte(&self, section: CartridgeSection) -> u8 {
+        self.section(section)[0]
+    }
+
+    pub fn entry_point(&self) -> &[u8] {
+        self.section(CartridgeSection::EntryPoint)
+    }
+
+    pub fn nintendo_logo(&self) -> &[u8] {
+        self.section(CartridgeSection::NintendoLogo)
+    }
+
+    // older cartridges use the manufacturer code and CGB flag bytes for a 16 character title
+    pub fn title(&self) -> String {
+        let mut title = self.section(CartridgeSection::Title).to_vec();
+        if self.manufacturer_code().is_none() {
+            title.extend_from_slice(self.section(CartridgeSection::ManufacturerCode));
+            if self.cgb_support() == CGBSupport::Unsupported {
+                title.push(self.byte(CartridgeSection::CGBFlag));
+            }
+        }
+        let end = title.iter().position(|&byte| byte == 0).unwrap_or(title.len());
+        String::from_utf8_lossy(&title[..end]).trim_end().to_string()
+    }
+
+    // all 16 bytes a title can take up, whatever they hold
+    pub fn title_bytes(&self) -> &[u8] {
+        &self.bytes[0x134 - HEADER_START..=0x143 - HEADER_START]
+    }
+
+    // only CGB era cartridges have one, four uppercase letters or digits
+    pub fn manufacturer_code(&self) -> Option<String> {
+        let code = self.section(CartridgeSection::ManufacturerCode);
+        if self.cgb_support() != CGBSupport::Unsupported
+            && code.iter().all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit())
+        {
+            Some(String::from_utf8_lossy(code).to_string())
+        } else {
+            None
+        }
+    }
+
+    pub fn cgb_support(&self) -> CGBSupport {
+        match self.byte(CartridgeSection::CGBFlag) {
+            0xC0 => CGBSupport::Exclusive,
+            flag if flag & 0x80 != 0 => CGBSupport::Enhanced,
+            _ => CGBSupport::Unsupported,
+        }
+    }
+
+    pub fn sgb_support(&self) -> bool {
+        self.byte(CartridgeSection::SGBFlag) == 0x03
+    }
+
+    pub fn cartridge_type(&self) -> CartridgeType {
+        CartridgeType::from_byte(self.byte(CartridgeSection::CartridgeType))
+    }
+
+    // in bytes, None for an unknown size code
+    pub fn rom_size(&self) -> Option<usize> {
+        match self.byte(CartridgeSection::ROMSize) {
+            code @ 0x00..=0x08 => Some(0x8000 << code),
+            0x52 => Some(72 * 0x4000),
+            0x53 => Some(80 * 0x4000),
+            0x54 => Some(96 * 0x4000),
+            _ => None,
+        }
+    }
+
+    // in bytes, None for an unknown size code
+    pub fn ram_size(&self) -> Option<usize> {
+        match self.byte(CartridgeSection::RAMSize) {
+            0x00 => Some(0),
+            0x01 => Some(0x800),
+            0x02 => Some(0x2000),
+            0x03 => Some(0x8000),
+            0x04 => Some(0x20000),
+            0x05 => Some(0x10000),
+            _ => None,
+        }
+    }
+
+    pub fn destination(&self) -> Destination {
+        match self.byte(CartridgeSection::DestinationCode) {
+            0x00 => Destination::Japan,
+            0x01 => Destination::Overseas,
+            code => Destination::Unknown(code),
+        }
+    }
+
+    pub fn old_licensee_code(&self) -> u8 {
+        self.byte(CartridgeSection::OldLicenseeCode)
+    }
+
+    pub fn new_licensee_code(&self) -> [u8; 2] {
+        self.section(CartridgeSection::NewLicenseeCode).try_into().unwrap()
+    }
+
+    // an old code of 0x33 means the new code is used instead
+    pub fn licensee(&self) -> Option<&'static str> {
+        match self.old_licensee_code() {
+            0x33 => new_licensee_name(self.new_licensee_code()),
+            code => old_licensee_name(code),
+        }
+    }
+
+    pub fn mask_rom_version(&self) -> u8 {
+        self.byte(CartridgeSection::MaskROMVersionNumber)
+    }
+
+    pub fn header_checksum(&self) -> u8 {
+        self.byte(CartridgeSection::HeaderChecksum)
+    }
+
+    // stored big-endian, unlike everything else
+    pub fn global_checksum(&self) -> u16 {
+        let bytes = self.section(CartridgeSection::GlobalChecksum);
+        u16::from_be_bytes([bytes[0], bytes[1]])
+    }
+
+    // what the boot ROM computes over 0x0134-0x014C
+    pub fn computed_header_checksum(&self) -> u8 {
+        self.bytes[0x134 - HEADER_START..=0x14C - HEADER_START]
+            .iter()
+            .fold(0u8, |checksum, &byte| checksum.wrapping_sub(byte).wrapping_sub(1))
+    }
+
+    // rom is the whole image, the global checksum adds up every byte but its own two
+    pub fn validate(&self, rom: &[u8]) -> ValidationReport {
+        let global_range = Cartridge::range_map(CartridgeSection::GlobalChecksum);
+        let actual = rom
+            .iter()
+            .enumerate()
+            .filter(|(address, _)| !global_range.contains(address))
+            .fold(0u16, |checksum, (_, &byte)| checksum.wrapping_add(byte as u16));
+
+        ValidationReport {
+            header_checksum: Checksum {
+                expected: self.header_checksum(),
+                actual: self.computed_header_checksum(),
+            },
+            global_checksum: Checksum {
+                expected: self.global_checksum(),
+                actual,
+            },
+            logo_valid: self.nintendo_logo() == NINTENDO_LOGO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cartridge::header::{
+        CartridgeHeader, CartridgeType, CGBSupport, Controller, Destination, NINTENDO_LOGO,
+    };
+
+    // a 32 KiB image with a correct logo and both checksums filled in
+    fn rom(title: &[u8], patch: &[(usize, u8)]) -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x104..0x134].copy_from_slice(&NINTENDO_LOGO);
+        rom[0x134..0x134 + title.len()].copy_from_slice(title);
+        for &(address, value) in patch {
+            rom[address] = value;
+        }
+        rom[0x14D] = CartridgeHeader::from_rom(&rom).unwrap().computed_header_checksum();
+        let global = rom.iter().fold(0u16, |checksum, &byte| checksum.wrapping_add(byte as u16));
+        rom[0x14E..0x150].copy_from_slice(&global.to_be_bytes());
+        rom
+    }
+
+    #[test]
+    fn decodes_fields() {
+        let rom = rom(b"POKEMON_SLVAAXJ", &[(0x143, 0x80), (0x146, 0x03), (0x147, 0x10), (0x148, 0x06), (0x149, 0x03), (0x14A, 0x01), (0x14B, 0x01)]);
+        let header = CartridgeHeader::from_rom(&rom).unwrap();
+
+        assert_eq!(header.title(), "POKEMON_SLV");
+        assert_eq!(header.manufacturer_code().as_deref(), Some("AAXJ"));
+        assert_eq!(header.cgb_support(), CGBSupport::Enhanced);
+        assert!(header.sgb_support());
+        assert_eq!(header.cartridge_type(), CartridgeType::MBC3TimerRAMBattery);
+        assert_eq!(header.cartridge_type().controller(), Controller::MBC3);
+        assert!(header.cartridge_type().has_timer() && header.cartridge_type().has_battery());
+        assert_eq!(header.rom_size(), Some(0x200000));
+        assert_eq!(header.ram_size(), Some(0x8000));
+        assert_eq!(header.destination(), Destination::Overseas);
+        assert_eq!(header.licensee(), Some("Nintendo"));
+    }
+
+    #[test]
+    fn old_titles_run_into_the_manufacturer_code() {
+        let rom = rom(b"SUPER MARIOLAND", &[]);
+        let header = CartridgeHeader::from_rom(&rom).unwrap();
+        assert_eq!(header.title(), "SUPER MARIOLAND");
+        assert_eq!(header.manufacturer_code(), None);
+        assert_eq!(header.cgb_support(), CGBSupport::Unsupported);
+    }
+
+    #[test]
+    fn new_licensee_code_is_used_with_0x33() {
+        let rom = rom(b"TEST", &[(0x144, b'A'), (0x145, b'4'), (0x14B, 0x33)]);
+        let header = CartridgeHeader::from_rom(&rom).unwrap();
+        assert_eq!(header.licensee(), Some("Konami"));
+    }
+
+    #[test]
+    fn validates_checksums_and_logo() {
+        let mut rom = rom(b"TEST", &[]);
+        let report = CartridgeHeader::from_rom(&rom).unwrap().validate(&rom);
+        assert!(report.is_valid());
+
+        rom[0x104] = 0;
+        rom[0x7FFF] = 1;
+        let report = CartridgeHeader::from_rom(&rom).unwrap().validate(&rom);
+        assert!(report.header_checksum.is_valid());
+        assert!(!report.global_checksum.is_valid());
+        assert!(!report.logo_valid);
+        assert!(!report.boots());
+    }
+}
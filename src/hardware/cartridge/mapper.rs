@@ -0,0 +1,61 @@
+use crate::hardware::cartridge::rtc::RTC;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+// the memory bank controller on the cartridge, sees every access to 0x0000-0x7FFF and 0xA000-0xBFFF
+pub trait Mapper {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8;
+    fn write_rom(&mut self, address: u16, value: u8);
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8;
+    // true when the write landed, false when it went nowhere (RAM disabled or missing)
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool;
+
+    // rumble motor on (true) and off (false) since the last call, only rumble carts have one
+    fn drain_rumble_events(&mut self) -> Vec<bool> {
+        Vec::new()
+    }
+
+    fn rtc(&mut self) -> Option<&mut RTC> {
+        None
+    }
+}
+
+// a byte of the 16 KiB ROM bank, bank numbers wrap around the size of the ROM
+pub fn read_rom_bank(rom: &[u8], bank: usize, address: u16) -> u8 {
+    let banks = (rom.len() / ROM_BANK_SIZE).max(1);
+    let offset = (bank % banks) * ROM_BANK_SIZE + (address as usize & (ROM_BANK_SIZE - 1));
+    rom.get(offset).copied().unwrap_or(0xFF)
+}
+
+// offset of a byte of the 8 KiB RAM bank, None without any RAM, smaller RAMs are mirrored
+pub fn ram_offset(ram: &[u8], bank: usize, address: u16) -> Option<usize> {
+    if ram.is_empty() {
+        return None;
+    }
+    Some((bank * RAM_BANK_SIZE + (address as usize & (RAM_BANK_SIZE - 1))) % ram.len())
+}
+
+// no controller, the ROM is wired straight to the bus
+pub struct RomOnly;
+
+impl Mapper for RomOnly {
+    // reads past the end of a short image float high
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        rom.get(address as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_rom(&mut self, _address: u16, _value: u8) {}
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        ram_offset(ram, 0, address).map_or(0xFF, |offset| ram[offset])
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        let Some(offset) = ram_offset(ram, 0, address) else {
+            return false;
+        };
+        ram[offset] = value;
+        true
+    }
+}
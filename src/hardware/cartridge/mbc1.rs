@@ -0,0 +1,158 @@
+use crate::hardware::cartridge::mapper::{Mapper, ram_offset, read_rom_bank};
+
+pub struct MBC1 {
+    ram_enabled: bool,
+    // BANK1, the low 5 bits of the ROM bank, never 0
+    bank1: u8,
+    // BANK2, bits 5-6 of the ROM bank, or the RAM bank
+    bank2: u8,
+    // in mode 1 BANK2 also applies to 0x0000-0x3FFF and to RAM
+    mode: bool,
+    // MBC1M wires BANK2 to bits 4-5 and leaves bit 4 of BANK1 unconnected
+    multicart: bool,
+}
+
+impl MBC1 {
+    pub fn new(multicart: bool) -> Self {
+        MBC1 {
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            mode: false,
+            multicart,
+        }
+    }
+
+    // multicarts are 1 MiB and have another game's header, logo included, at bank 0x10
+    pub fn is_multicart(rom: &[u8]) -> bool {
+        const LOGO: std::ops::Range<usize> = 0x104..0x134;
+        rom.len() == 0x100000 && rom[0x40000 + LOGO.start..0x40000 + LOGO.end] == rom[LOGO]
+    }
+
+    fn bank2_shift(&self) -> u8 {
+        if self.multicart { 4 } else { 5 }
+    }
+
+    fn bank1_mask(&self) -> u8 {
+        if self.multicart { 0x0F } else { 0x1F }
+    }
+}
+
+impl Mapper for MBC1 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = if address < 0x4000 {
+            if self.mode { self.bank2 << self.bank2_shift() } else { 0 }
+        } else {
+            self.bank2 << self.bank2_shift() | (self.bank1 & self.bank1_mask())
+        };
+        read_rom_bank(rom, bank as usize, address)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            // the zero check looks at all 5 bits, so bank 0x20 can't be selected but 0x10 on a multicart can
+            0x2000..=0x3FFF => self.bank1 = if value & 0x1F == 0 { 1 } else { value & 0x1F },
+            0x4000..=0x5FFF => self.bank2 = value & 0b11,
+            _ => self.mode = value & 1 != 0,
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        let bank = if self.mode { self.bank2 as usize } else { 0 };
+        match ram_offset(ram, bank, address) {
+            Some(offset) if self.ram_enabled => ram[offset],
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        let bank = if self.mode { self.bank2 as usize } else { 0 };
+        match ram_offset(ram, bank, address) {
+            Some(offset) if self.ram_enabled => {
+                ram[offset] = value;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cartridge::mapper::{Mapper, ROM_BANK_SIZE};
+    use crate::hardware::cartridge::mbc1::MBC1;
+
+    // every bank starts with its own number
+    fn numbered_rom(banks: usize) -> Vec<u8> {
+        let mut rom = vec![0; banks * ROM_BANK_SIZE];
+        for bank in 0..banks {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        rom
+    }
+
+    #[test]
+    fn switches_rom_banks() {
+        let rom = numbered_rom(128);
+        let mut mbc = MBC1::new(false);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 1);
+
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 1);
+        mbc.write_rom(0x2000, 0x05);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 5);
+        mbc.write_rom(0x4000, 0x02);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x45);
+        // bank 0x40 is remapped to 0x41
+        mbc.write_rom(0x2000, 0x20);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x41);
+
+        // the fixed bank only moves in mode 1
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0x40);
+    }
+
+    #[test]
+    fn bank_numbers_wrap_around_small_roms() {
+        let rom = numbered_rom(4);
+        let mut mbc = MBC1::new(false);
+        mbc.write_rom(0x2000, 0x06);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 2);
+    }
+
+    #[test]
+    fn ram_needs_enabling_and_banks_in_mode_1() {
+        let mut ram = vec![0; 0x8000];
+        let mut mbc = MBC1::new(false);
+        mbc.write_ram(&mut ram, 0xA000, 0x12);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(&mut ram, 0xA000, 0x12);
+        mbc.write_rom(0x4000, 0x03);
+        mbc.write_ram(&mut ram, 0xA000, 0x34);
+        assert_eq!(ram[0], 0x34);
+
+        mbc.write_rom(0x6000, 0x01);
+        mbc.write_ram(&mut ram, 0xA000, 0x56);
+        assert_eq!(ram[0x6000], 0x56);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x56);
+
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+    }
+
+    #[test]
+    fn multicart_uses_4_bit_bank1() {
+        let rom = numbered_rom(64);
+        let mut mbc = MBC1::new(true);
+        mbc.write_rom(0x2000, 0x12);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x02);
+        mbc.write_rom(0x4000, 0x01);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x12);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0x10);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::hardware::cartridge::mapper::{Mapper, read_rom_bank};
+
+// 512 half-bytes built into the controller
+pub const MBC2_RAM_SIZE: usize = 0x200;
+
+pub struct MBC2 {
+    ram_enabled: bool,
+    // 4 bits, 0 selects bank 1
+    rom_bank: u8,
+}
+
+impl MBC2 {
+    pub fn new() -> Self {
+        MBC2 {
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+}
+
+impl Default for MBC2 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mapper for MBC2 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = if address < 0x4000 { 0 } else { self.rom_bank as usize };
+        read_rom_bank(rom, bank, address)
+    }
+
+    // both registers live in 0x0000-0x3FFF, address bit 8 picks which one is written
+    fn write_rom(&mut self, address: u16, value: u8) {
+        if address >= 0x4000 {
+            return;
+        }
+        if address & 0x0100 == 0 {
+            self.ram_enabled = value & 0x0F == 0x0A;
+        } else {
+            self.rom_bank = if value & 0x0F == 0 { 1 } else { value & 0x0F };
+        }
+    }
+
+    // only the lower nibble exists, the upper one floats high, and 0xA200-0xBFFF mirrors it
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        match ram.get(address as usize & (MBC2_RAM_SIZE - 1)) {
+            Some(value) if self.ram_enabled => value | 0xF0,
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        match ram.get_mut(address as usize & (MBC2_RAM_SIZE - 1)) {
+            Some(cell) if self.ram_enabled => {
+                *cell = value & 0x0F;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cartridge::mapper::{Mapper, ROM_BANK_SIZE};
+    use crate::hardware::cartridge::mbc2::{MBC2, MBC2_RAM_SIZE};
+
+    #[test]
+    fn address_bit_8_selects_register() {
+        // every bank starts with its own number
+        let mut rom = vec![0; 16 * ROM_BANK_SIZE];
+        for bank in 0..16 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        let mut ram = vec![0; MBC2_RAM_SIZE];
+        let mut mbc = MBC2::new();
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 1);
+
+        // bit 8 set, the ROM bank register
+        mbc.write_rom(0x2100, 0x05);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 5);
+        mbc.write_rom(0x3F00, 0x0C);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 12);
+        mbc.write_rom(0x0100, 0x00);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 1);
+
+        // bit 8 clear, the RAM enable register, the bank stays put
+        mbc.write_rom(0x2100, 0x07);
+        mbc.write_rom(0x2000, 0x0A);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 7);
+        assert!(mbc.write_ram(&mut ram, 0xA000, 0x03));
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 7);
+        assert!(!mbc.write_ram(&mut ram, 0xA000, 0x03));
+    }
+
+    #[test]
+    fn ram_is_4_bits_wide_and_mirrored() {
+        let mut ram = vec![0; MBC2_RAM_SIZE];
+        let mut mbc = MBC2::new();
+        mbc.write_rom(0x0000, 0x0A);
+
+        mbc.write_ram(&mut ram, 0xA010, 0xAB);
+        assert_eq!(ram[0x10], 0x0B);
+        assert_eq!(mbc.read_ram(&ram, 0xA010), 0xFB);
+        assert_eq!(mbc.read_ram(&ram, 0xA210), 0xFB);
+        assert_eq!(mbc.read_ram(&ram, 0xBE10), 0xFB);
+
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA010), 0xFF);
+    }
+}
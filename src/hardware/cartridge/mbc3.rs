@@ -0,0 +1,137 @@
+use crate::hardware::cartridge::mapper::{Mapper, ram_offset, read_rom_bank};
+use crate::hardware::cartridge::rtc::{RTC, RTCRegister};
+
+pub struct MBC3 {
+    // also enables the RTC registers
+    ram_enabled: bool,
+    // 7 bits, 0 selects bank 1
+    rom_bank: u8,
+    // 0x00-0x03 select a RAM bank, 0x08-0x0C an RTC register
+    select: u8,
+    // the last value written to the latch register, latching happens on a 0x00 then 0x01 write
+    latch: u8,
+    rtc: Option<RTC>,
+}
+
+impl MBC3 {
+    pub fn new(rtc: Option<RTC>) -> Self {
+        MBC3 {
+            ram_enabled: false,
+            rom_bank: 1,
+            select: 0,
+            latch: 0xFF,
+            rtc,
+        }
+    }
+
+    fn selects_rtc(&self) -> bool {
+        (RTCRegister::Seconds as u8..=RTCRegister::DayHigh as u8).contains(&self.select)
+    }
+}
+
+impl Mapper for MBC3 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = if address < 0x4000 { 0 } else { self.rom_bank as usize };
+        read_rom_bank(rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = if value & 0x7F == 0 { 1 } else { value & 0x7F },
+            0x4000..=0x5FFF => self.select = value,
+            _ => {
+                if self.latch == 0x00 && value == 0x01 {
+                    if let Some(rtc) = &mut self.rtc {
+                        rtc.latch();
+                    }
+                }
+                self.latch = value;
+            }
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        if self.selects_rtc() {
+            return self.rtc.as_ref().map_or(0xFF, |rtc| rtc.read(self.select));
+        }
+        match ram_offset(ram, (self.select & 0b11) as usize, address) {
+            Some(offset) if self.select <= 0x03 => ram[offset],
+            _ => 0xFF,
+        }
+    }
+
+    // the clock is saved along with RAM, so writing it counts too
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        if !self.ram_enabled {
+            return false;
+        }
+        if self.selects_rtc() {
+            let Some(rtc) = &mut self.rtc else {
+                return false;
+            };
+            rtc.write(self.select, value);
+            return true;
+        }
+        match ram_offset(ram, (self.select & 0b11) as usize, address) {
+            Some(offset) if self.select <= 0x03 => {
+                ram[offset] = value;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn rtc(&mut self) -> Option<&mut RTC> {
+        self.rtc.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cartridge::mapper::{Mapper, ROM_BANK_SIZE};
+    use crate::hardware::cartridge::mbc3::MBC3;
+    use crate::hardware::cartridge::rtc::{RTC, VirtualClock};
+
+    #[test]
+    fn switches_rom_and_ram_banks() {
+        let mut rom = vec![0; 128 * ROM_BANK_SIZE];
+        rom[0x7F * ROM_BANK_SIZE] = 0x7F;
+        let mut ram = vec![0; 0x8000];
+        let mut mbc = MBC3::new(None);
+
+        mbc.write_rom(0x2000, 0x7F);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x7F);
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x02);
+        mbc.write_ram(&mut ram, 0xA001, 0x42);
+        assert_eq!(ram[0x4001], 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0xA001), 0x42);
+    }
+
+    #[test]
+    fn rtc_reads_latched_time() {
+        let clock = VirtualClock::new();
+        let mut mbc = MBC3::new(Some(RTC::new(Box::new(clock.clone()))));
+        let ram = vec![];
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x08);
+
+        clock.advance(30);
+        mbc.write_rom(0x6000, 0x00);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 30);
+
+        // only a 0 to 1 write latches again
+        clock.advance(5);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 30);
+        mbc.write_rom(0x6000, 0x00);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 35);
+    }
+}
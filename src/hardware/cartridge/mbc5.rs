@@ -0,0 +1,128 @@
+use crate::hardware::cartridge::mapper::{Mapper, ram_offset, read_rom_bank};
+
+const RUMBLE_MOTOR: u8 = 0b1000;
+
+pub struct MBC5 {
+    ram_enabled: bool,
+    // 9 bits, unlike the older mappers bank 0 can be selected
+    rom_bank: u16,
+    ram_bank: u8,
+    // on rumble carts bit 3 of the RAM bank register drives the motor instead
+    rumble: bool,
+    motor: bool,
+    // motor state changes since the frontend last looked
+    rumble_events: Vec<bool>,
+}
+
+impl MBC5 {
+    pub fn new(rumble: bool) -> Self {
+        MBC5 {
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rumble,
+            motor: false,
+            rumble_events: Vec::new(),
+        }
+    }
+}
+
+impl Mapper for MBC5 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = if address < 0x4000 { 0 } else { self.rom_bank as usize };
+        read_rom_bank(rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | value as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | ((value as u16 & 1) << 8),
+            0x4000..=0x5FFF => {
+                if self.rumble {
+                    let motor = value & RUMBLE_MOTOR != 0;
+                    if motor != self.motor {
+                        self.motor = motor;
+                        self.rumble_events.push(motor);
+                    }
+                    self.ram_bank = value & 0b0111;
+                } else {
+                    self.ram_bank = value & 0b1111;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        match ram_offset(ram, self.ram_bank as usize, address) {
+            Some(offset) if self.ram_enabled => ram[offset],
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        match ram_offset(ram, self.ram_bank as usize, address) {
+            Some(offset) if self.ram_enabled => {
+                ram[offset] = value;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn drain_rumble_events(&mut self) -> Vec<bool> {
+        std::mem::take(&mut self.rumble_events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cartridge::mapper::{Mapper, ROM_BANK_SIZE};
+    use crate::hardware::cartridge::mbc5::MBC5;
+
+    #[test]
+    fn switches_9_bit_rom_banks() {
+        let mut rom = vec![0; 512 * ROM_BANK_SIZE];
+        rom[0x1FF * ROM_BANK_SIZE] = 0xAA;
+        rom[0x100 * ROM_BANK_SIZE] = 0xBB;
+        let mut mbc = MBC5::new(false);
+
+        mbc.write_rom(0x2000, 0xFF);
+        mbc.write_rom(0x3000, 0x01);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0xAA);
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0xBB);
+        // bank 0 is not remapped
+        mbc.write_rom(0x3000, 0x00);
+        rom[0] = 0xCC;
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0xCC);
+    }
+
+    #[test]
+    fn switches_ram_banks() {
+        let mut ram = vec![0; 16 * 0x2000];
+        let mut mbc = MBC5::new(false);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x0F);
+        mbc.write_ram(&mut ram, 0xA000, 0x42);
+        assert_eq!(ram[15 * 0x2000], 0x42);
+    }
+
+    #[test]
+    fn rumble_reports_motor_changes() {
+        let mut ram = vec![0; 8 * 0x2000];
+        let mut mbc = MBC5::new(true);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x0B);
+        mbc.write_rom(0x4000, 0x0A);
+        mbc.write_rom(0x4000, 0x02);
+        assert_eq!(mbc.drain_rumble_events(), vec![true, false]);
+        assert!(mbc.drain_rumble_events().is_empty());
+
+        // the motor bit doesn't select a bank
+        mbc.write_rom(0x4000, 0x0B);
+        mbc.write_ram(&mut ram, 0xA000, 0x42);
+        assert_eq!(ram[3 * 0x2000], 0x42);
+    }
+}
@@ -1,37 +1,39 @@
-use std::borrow::{Borrow, Cow};
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::Read;
-use std::iter::Copied;
-use std::ops::{Range, RangeInclusive};
-use std::path::Path;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use crate::hardware::cartridge::header::{CartridgeHeader, Controller, HEADER_END, ValidationReport};
+use crate::hardware::cartridge::mapper::{Mapper, RomOnly};
+use crate::hardware::cartridge::mbc1::MBC1;
+use crate::hardware::cartridge::mbc2::{MBC2, MBC2_RAM_SIZE};
+use crate::hardware::cartridge::mbc3::MBC3;
+use crate::hardware::cartridge::mbc5::MBC5;
+use crate::hardware::cartridge::rtc::{RTC, SystemClock};
+
+pub mod header;
+pub mod licensee;
+pub mod mapper;
+pub mod mbc1;
+pub mod mbc2;
+pub mod mbc3;
+pub mod mbc5;
+pub mod rtc;
 
 pub struct Cartridge {
     filename: String,
     pub header: CartridgeHeader,
-    data: Vec<u8>
-}
-
-#[derive(Debug)]
-pub struct CartridgeHeader {
-    entry_point: [u8; 4],
-    nintendo_logo: [u8; 48],
-    title: String,
-    manufacturer_code: [u8; 4],
-    cgb_flag: u8,
-    new_licensee_code: [u8; 2],
-    sgb_flag: u8,
-    cartridge_type: u8,
-    rom_size: u8,
-    ram_size: u8,
-    destination_code: u8,
-    old_licensee_code: u8,
-    mask_rom_version_number: u8,
-    header_checksum: u8,
-    global_checksum: [u8; 2],
+    data: Vec<u8>,
+    ram: Vec<u8>,
+    mapper: Box<dyn Mapper>,
+    // the sibling .sav file of battery-backed cartridges
+    save_path: Option<PathBuf>,
+    // RAM was written since the last save
+    ram_dirty: bool,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum CartridgeSection {
     EntryPoint,
     NintendoLogo,
@@ -50,117 +52,295 @@ pub enum CartridgeSection {
     GlobalChecksum,
 }
 
+#[derive(Debug)]
+pub enum CartridgeError {
+    IO(io::Error),
+    TooSmall { size: usize },
+    ROMSizeMismatch { expected: usize, actual: usize },
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::IO(error) => write!(f, "could not read ROM: {}", error),
+            CartridgeError::TooSmall { size } => {
+                write!(f, "ROM is {} bytes, too small to hold a header (0x{:X} bytes)", size, HEADER_END)
+            }
+            CartridgeError::ROMSizeMismatch { expected, actual } => {
+                write!(f, "header declares a {} byte ROM but the image is {} bytes", expected, actual)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CartridgeError::IO(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CartridgeError {
+    fn from(error: io::Error) -> Self {
+        CartridgeError::IO(error)
+    }
+}
+
 impl Cartridge {
     pub fn range_map(section: CartridgeSection) -> RangeInclusive<usize> {
-        let mut map: HashMap<CartridgeSection, RangeInclusive<usize>> = HashMap::new();
-        map.insert(CartridgeSection::EntryPoint, 0x100..=0x103);
-        map.insert(CartridgeSection::NintendoLogo, 0x104..=0x133);
-        map.insert(CartridgeSection::Title, 0x134..=0x143);
-        map.insert(CartridgeSection::ManufacturerCode, 0x013F..=0x142);
-        map.insert(CartridgeSection::CGBFlag, 0x143..=0x143);
-        map.insert(CartridgeSection::NewLicenseeCode, 0x144..=0x145);
-        map.insert(CartridgeSection::SGBFlag, 0x146..=0x146);
-        map.insert(CartridgeSection::CartridgeType, 0x147..=0x147);
-        map.insert(CartridgeSection::ROMSize, 0x148..=0x148);
-        map.insert(CartridgeSection::RAMSize, 0x149..=0x149);
-        map.insert(CartridgeSection::DestinationCode, 0x14A..=0x14A);
-        map.insert(CartridgeSection::OldLicenseeCode, 0x14B..=0x14B);
-        map.insert(CartridgeSection::MaskROMVersionNumber, 0x14C..=0x14C);
-        map.insert(CartridgeSection::HeaderChecksum, 0x14D..=0x14D);
-        map.insert(CartridgeSection::GlobalChecksum, 0x14E..=0x14F);
-
-        map.remove(&section).unwrap()
-    }
-
-    pub fn get_title(&self) -> &String {
-        &self.header.title
+        match section {
+            CartridgeSection::EntryPoint => 0x100..=0x103,
+            CartridgeSection::NintendoLogo => 0x104..=0x133,
+            // 0x134-0x143 on older cartridges, see CartridgeHeader::title
+            CartridgeSection::Title => 0x134..=0x13E,
+            CartridgeSection::ManufacturerCode => 0x13F..=0x142,
+            CartridgeSection::CGBFlag => 0x143..=0x143,
+            CartridgeSection::NewLicenseeCode => 0x144..=0x145,
+            CartridgeSection::SGBFlag => 0x146..=0x146,
+            CartridgeSection::CartridgeType => 0x147..=0x147,
+            CartridgeSection::ROMSize => 0x148..=0x148,
+            CartridgeSection::RAMSize => 0x149..=0x149,
+            CartridgeSection::DestinationCode => 0x14A..=0x14A,
+            CartridgeSection::OldLicenseeCode => 0x14B..=0x14B,
+            CartridgeSection::MaskROMVersionNumber => 0x14C..=0x14C,
+            CartridgeSection::HeaderChecksum => 0x14D..=0x14D,
+            CartridgeSection::GlobalChecksum => 0x14E..=0x14F,
+        }
+    }
+
+    pub fn get_title(&self) -> String {
+        self.header.title()
     }
     pub fn get_header(&self) -> &CartridgeHeader { &self.header }
 
-    #[cfg(target_family = "windows")]
-    pub fn load(path: &Path) -> Self {
-        let mut data = vec![];
-        File::open(path).and_then(|mut f| f.read_to_end(&mut data)).map_err(|_| "Could not read ROM");
+    pub fn get_filename(&self) -> &str {
+        &self.filename
+    }
 
-        let entry_point_range = Cartridge::range_map(CartridgeSection::EntryPoint);
-        let entry_point = data[entry_point_range].try_into().unwrap();
+    // checks the header checksum, the global checksum and the logo
+    pub fn validate(&self) -> ValidationReport {
+        self.header.validate(&self.data)
+    }
 
-        let nintendo_logo_range = Cartridge::range_map(CartridgeSection::NintendoLogo);
-        let nintendo_logo = data[nintendo_logo_range].try_into().unwrap();
+    // the memory bank controller wired up according to the cartridge type
+    fn mapper_for(header: &CartridgeHeader, data: &[u8]) -> Box<dyn Mapper> {
+        let cartridge_type = header.cartridge_type();
+        match cartridge_type.controller() {
+            Controller::MBC1 => Box::new(MBC1::new(MBC1::is_multicart(data))),
+            Controller::MBC2 => Box::new(MBC2::new()),
+            Controller::MBC3 if cartridge_type.has_timer() => Box::new(MBC3::new(Some(RTC::new(Box::new(SystemClock))))),
+            Controller::MBC3 => Box::new(MBC3::new(None)),
+            Controller::MBC5 => Box::new(MBC5::new(cartridge_type.has_rumble())),
+            _ => Box::new(RomOnly),
+        }
+    }
 
-        let title_range: RangeInclusive<usize> = Cartridge::range_map(CartridgeSection::Title);
-        let title: String =
-            String::from_utf8_lossy(&data[title_range])
-                .trim_matches(char::from(0))
-                .to_string();
+    // external RAM as declared by the header, MBC2 carts declare none but have it built in
+    fn ram_for(header: &CartridgeHeader) -> Vec<u8> {
+        let size = match header.cartridge_type().controller() {
+            Controller::MBC2 => MBC2_RAM_SIZE,
+            _ => header.ram_size().unwrap_or(0),
+        };
+        vec![0; size]
+    }
 
-        let manufacturer_code_range = Cartridge::range_map(CartridgeSection::ManufacturerCode);
-        let manufacturer_code  = data[manufacturer_code_range].try_into().unwrap();
+    // reads a ROM from disk, battery-backed cartridges also pick up the .sav next to it
+    pub fn load(path: &Path) -> Result<Self, CartridgeError> {
+        let mut cartridge = Cartridge::from_bytes(fs::read(path)?)?;
+        cartridge.filename = path.file_name().map_or(String::new(), |name| name.to_string_lossy().to_string());
 
-        let cgb_flag_range = Cartridge::range_map(CartridgeSection::CGBFlag);
-        let cgb_flag = data[cgb_flag_range][0];
+        if cartridge.header.cartridge_type().has_battery() {
+            let save_path = path.with_extension("sav");
+            if let Ok(save) = fs::read(&save_path) {
+                cartridge.load_save_data(&save);
+            }
+            cartridge.save_path = Some(save_path);
+        }
+        Ok(cartridge)
+    }
 
-        let new_licensee_code_range = Cartridge::range_map(CartridgeSection::NewLicenseeCode);
-        let new_licensee_code = data[new_licensee_code_range].try_into().unwrap();
+    // an in-memory ROM image, the header's ROM size has to match its length
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, CartridgeError> {
+        let header = CartridgeHeader::from_rom(&data).ok_or(CartridgeError::TooSmall { size: data.len() })?;
+        match header.rom_size() {
+            Some(expected) if data.len() != expected => {
+                return Err(CartridgeError::ROMSizeMismatch { expected, actual: data.len() })
+            }
+            _ => {}
+        }
 
-        let sgb_flag_range = Cartridge::range_map(CartridgeSection::SGBFlag);
-        let sgb_flag = data[sgb_flag_range][0];
+        Ok(Cartridge {
+            filename: String::new(),
+            mapper: Cartridge::mapper_for(&header, &data),
+            ram: Cartridge::ram_for(&header),
+            header,
+            data,
+            save_path: None,
+            ram_dirty: false,
+        })
+    }
 
-        let cartridge_type_range = Cartridge::range_map(CartridgeSection::CartridgeType);
-        let cartridge_type: u8 = data[cartridge_type_range][0];
+    // 0x0000-0x7FFF
+    pub fn read(&self, address: u16) -> u8 {
+        self.mapper.read_rom(&self.data, address)
+    }
 
-        let rom_size_range = Cartridge::range_map(CartridgeSection::ROMSize);
-        let rom_size: u8 = data[rom_size_range][0];
+    // writes to ROM go to the mapper's registers
+    pub fn write(&mut self, address: u16, value: u8) {
+        self.mapper.write_rom(address, value);
+    }
 
-        let ram_size_range = Cartridge::range_map(CartridgeSection::RAMSize);
-        let ram_size: u8 = data[ram_size_range][0];
+    // 0xA000-0xBFFF
+    pub fn read_ram(&self, address: u16) -> u8 {
+        self.mapper.read_ram(&self.ram, address)
+    }
 
-        let destination_code_range = Cartridge::range_map(CartridgeSection::DestinationCode);
-        let destination_code: u8 = data[destination_code_range][0];
+    pub fn write_ram(&mut self, address: u16, value: u8) {
+        if self.mapper.write_ram(&mut self.ram, address, value) {
+            self.ram_dirty = true;
+        }
+    }
 
-        let old_licensee_code_range = Cartridge::range_map(CartridgeSection::OldLicenseeCode);
-        let old_licensee_code: u8 = data[old_licensee_code_range][0];
+    pub fn has_save(&self) -> bool {
+        self.save_path.is_some()
+    }
 
-        let mask_rom_version_number_range = Cartridge::range_map(CartridgeSection::MaskROMVersionNumber);
-        let mask_rom_version_number: u8 = data[mask_rom_version_number_range][0];
+    // whether RAM changed since the last save, frontends use it to save periodically
+    pub fn needs_saving(&self) -> bool {
+        self.save_path.is_some() && self.ram_dirty
+    }
 
-        let header_checksum_range = Cartridge::range_map(CartridgeSection::HeaderChecksum);
-        let header_checksum: u8 = data[header_checksum_range][0];
+    // the contents of a .sav file: RAM followed by the RTC block when there is a clock
+    pub fn save_data(&mut self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        if let Some(rtc) = self.mapper.rtc() {
+            data.extend(rtc.save());
+        }
+        data
+    }
+
+    // a short file fills what it can, a missing RTC block leaves the clock alone
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        let length = self.ram.len().min(data.len());
+        self.ram[..length].copy_from_slice(&data[..length]);
+        if let Some(rtc) = self.mapper.rtc() {
+            rtc.load(&data[length..]);
+        }
+    }
+
+    // writes the .sav file of battery-backed carts, through a temporary file so a crash midway
+    // can't leave a truncated save behind
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(save_path) = self.save_path.clone() else { return Ok(()) };
+        let temporary = save_path.with_extension("sav.tmp");
+        fs::write(&temporary, self.save_data())?;
+        fs::rename(&temporary, &save_path)?;
+        self.ram_dirty = false;
+        Ok(())
+    }
+
+    // rumble motor changes for the frontend, true when it turns on
+    pub fn drain_rumble_events(&mut self) -> Vec<bool> {
+        self.mapper.drain_rumble_events()
+    }
+}
+#[cfg(test)]
+impl Cartridge {
+    // a 32 KiB ROM-only cartridge filled with NOPs
+    pub(crate) fn blank() -> Self {
+        Cartridge::with_program(0x0000, &[])
+    }
 
-        let global_checksum_range = Cartridge::range_map(CartridgeSection::GlobalChecksum);
-        let global_checksum = data[global_checksum_range].try_into().unwrap(); // TODO: should return a 16bit global_checksum
+    // a blank cartridge with program copied in at address
+    pub(crate) fn with_program(address: u16, program: &[u8]) -> Self {
+        let mut data = vec![0; 0x8000];
+        data[address as usize..address as usize + program.len()].copy_from_slice(program);
+        Cartridge::with_type(0x00, 0x00, data)
+    }
 
+    // an image with an otherwise all-zero header
+    pub(crate) fn with_type(cartridge_type: u8, ram_size: u8, mut data: Vec<u8>) -> Self {
+        data[0x147] = cartridge_type;
+        data[0x149] = ram_size;
+        let header = CartridgeHeader::from_rom(&data).unwrap();
         Cartridge {
-            filename: path.file_name().unwrap().to_str().unwrap().to_string(),
-            header: CartridgeHeader {
-                entry_point,
-                nintendo_logo,
-                title,
-                manufacturer_code,
-                cgb_flag,
-                new_licensee_code,
-                sgb_flag,
-                cartridge_type,
-                rom_size,
-                ram_size,
-                destination_code,
-                old_licensee_code,
-                mask_rom_version_number,
-                header_checksum,
-                global_checksum,
-            },
-            data
+            filename: String::new(),
+            mapper: Cartridge::mapper_for(&header, &data),
+            ram: Cartridge::ram_for(&header),
+            header,
+            data,
+            save_path: None,
+            ram_dirty: false,
         }
     }
+}
 
-    pub fn read(&self, address: u16) -> u8 {
-        self.data[address as usize]
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cartridge::{Cartridge, CartridgeError};
+    use crate::hardware::cartridge::rtc::RTC_SAVE_SIZE;
+
+    #[test]
+    fn ram_is_sized_from_header() {
+        assert_eq!(Cartridge::with_type(0x03, 0x03, vec![0; 0x8000]).save_data().len(), 0x8000);
+        assert_eq!(Cartridge::with_type(0x1B, 0x04, vec![0; 0x8000]).save_data().len(), 0x20000);
+        assert_eq!(Cartridge::with_type(0x06, 0x00, vec![0; 0x8000]).save_data().len(), 0x200);
+        assert_eq!(Cartridge::with_type(0x00, 0x00, vec![0; 0x8000]).save_data().len(), 0);
     }
 
-    pub fn write(&mut self, address: u16, value: u8) {
-        self.data[address as usize] = value;
+    #[test]
+    fn save_data_round_trips() {
+        let mut cartridge = Cartridge::with_type(0x03, 0x02, vec![0; 0x8000]);
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write_ram(0xA123, 0x42);
+        let save = cartridge.save_data();
+
+        let mut loaded = Cartridge::with_type(0x03, 0x02, vec![0; 0x8000]);
+        loaded.load_save_data(&save);
+        loaded.write(0x0000, 0x0A);
+        assert_eq!(loaded.read_ram(0xA123), 0x42);
+    }
+
+    #[test]
+    fn only_stored_writes_dirty_ram() {
+        let mut cartridge = Cartridge::with_type(0x03, 0x02, vec![0; 0x8000]);
+        cartridge.write_ram(0xA000, 0x42);
+        assert!(!cartridge.ram_dirty);
+
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x42);
+        assert!(cartridge.ram_dirty);
     }
 
-    #[cfg(target_family = "wasm")]
-    pub fn load(path: &Path) -> Self {}
-}
\ No newline at end of file
+    #[test]
+    fn rtc_block_follows_ram() {
+        let mut cartridge = Cartridge::with_type(0x10, 0x03, vec![0; 0x8000]);
+        assert_eq!(cartridge.save_data().len(), 0x8000 + RTC_SAVE_SIZE);
+    }
+
+    #[test]
+    fn from_bytes_checks_the_image_size() {
+        assert!(matches!(Cartridge::from_bytes(vec![0; 0x100]), Err(CartridgeError::TooSmall { size: 0x100 })));
+
+        let mut data = vec![0; 0x8000];
+        data[0x148] = 0x01;
+        assert!(matches!(
+            Cartridge::from_bytes(data),
+            Err(CartridgeError::ROMSizeMismatch { expected: 0x10000, actual: 0x8000 })
+        ));
+
+        let mut data = vec![0; 0x10000];
+        data[0x148] = 0x01;
+        data[0x134..0x138].copy_from_slice(b"TEST");
+        let cartridge = Cartridge::from_bytes(data).unwrap();
+        assert_eq!(cartridge.get_title(), "TEST");
+    }
+
+    #[test]
+    fn load_reports_missing_files() {
+        let result = Cartridge::load(std::path::Path::new("roms/does-not-exist.gb"));
+        assert!(matches!(result, Err(CartridgeError::IO(_))));
+    }
+}
@@ -0,0 +1,252 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+// where the real-time clock gets the time from, in whole seconds
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+// the host's wall clock, what a cartridge with a battery would see
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[cfg(not(target_family = "wasm"))]
+    fn now(&self) -> u64 {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+    }
+
+    // SystemTime isn't available in the browser
+    #[cfg(target_family = "wasm")]
+    fn now(&self) -> u64 {
+        (stdweb::web::Date::now() / 1000.0) as u64
+    }
+}
+
+// a clock that only moves when told to, clones share the same time
+#[derive(Clone, Default)]
+pub struct VirtualClock {
+    seconds: Rc<Cell<u64>>,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, seconds: u64) {
+        self.seconds.set(self.seconds.get() + seconds);
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> u64 {
+        self.seconds.get()
+    }
+}
+
+pub enum RTCRegister {
+    Seconds = 0x08,
+    Minutes = 0x09,
+    Hours = 0x0A,
+    DayLow = 0x0B,
+    // bit 0 is bit 8 of the day counter, bit 6 halts the clock, bit 7 is the day counter carry
+    DayHigh = 0x0C,
+}
+
+// appended to the save after the RAM, see RTC::save
+pub const RTC_SAVE_SIZE: usize = 48;
+
+const HALT: u8 = 0b0100_0000;
+const DAY_CARRY: u8 = 0b1000_0000;
+
+// the MBC3 real-time clock, counters are only brought up to date when they are latched or written
+pub struct RTC {
+    clock: Box<dyn Clock>,
+    // the clock's reading the counters were last brought up to date with
+    last_update: u64,
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16,
+    halted: bool,
+    carry: bool,
+    // what reads see, in register order
+    latched: [u8; 5],
+}
+
+impl RTC {
+    pub fn new(clock: Box<dyn Clock>) -> Self {
+        RTC {
+            last_update: clock.now(),
+            clock,
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            halted: false,
+            carry: false,
+            latched: [0; 5],
+        }
+    }
+
+    fn update(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.last_update);
+        self.last_update = now;
+        if self.halted {
+            return;
+        }
+
+        let seconds = self.seconds as u64 + elapsed;
+        let minutes = self.minutes as u64 + seconds / 60;
+        let hours = self.hours as u64 + minutes / 60;
+        let days = self.days as u64 + hours / 24;
+        self.seconds = (seconds % 60) as u8;
+        self.minutes = (minutes % 60) as u8;
+        self.hours = (hours % 24) as u8;
+        // the carry stays set until it is cleared by a write
+        if days > 0x1FF {
+            self.carry = true;
+        }
+        self.days = (days % 0x200) as u16;
+    }
+
+    // the live counters, in register order
+    fn registers(&self) -> [u8; 5] {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days as u8,
+            (self.carry as u8) << 7 | (self.halted as u8) << 6 | (self.days >> 8) as u8,
+        ]
+    }
+
+    // copies the counters as of now into the registers reads see
+    pub fn latch(&mut self) {
+        self.update();
+        self.latched = self.registers();
+    }
+
+    // the layout VBA-M, BGB and others append to saves: the live then the latched registers as
+    // 32-bit little-endian words, then the time of saving as a 64-bit UNIX timestamp
+    pub fn save(&mut self) -> Vec<u8> {
+        self.update();
+        let mut block = Vec::with_capacity(RTC_SAVE_SIZE);
+        for value in self.registers().iter().chain(self.latched.iter()) {
+            block.extend_from_slice(&(*value as u32).to_le_bytes());
+        }
+        block.extend_from_slice(&self.last_update.to_le_bytes());
+        block
+    }
+
+    // also takes the older 44 byte layout with a 32-bit timestamp, the time since then is caught up
+    pub fn load(&mut self, block: &[u8]) {
+        if block.len() < 44 {
+            return;
+        }
+        let word = |index: usize| block[index * 4];
+        self.seconds = word(0) & 0x3F;
+        self.minutes = word(1) & 0x3F;
+        self.hours = word(2) & 0x1F;
+        self.days = ((word(4) as u16 & 1) << 8) | word(3) as u16;
+        self.halted = word(4) & HALT != 0;
+        self.carry = word(4) & DAY_CARRY != 0;
+        for (index, latched) in self.latched.iter_mut().enumerate() {
+            *latched = word(5 + index);
+        }
+        self.last_update = match block.len() {
+            44..=47 => u32::from_le_bytes(block[40..44].try_into().unwrap()) as u64,
+            _ => u64::from_le_bytes(block[40..48].try_into().unwrap()),
+        };
+    }
+
+    pub fn read(&self, register: u8) -> u8 {
+        self.latched[(register - RTCRegister::Seconds as u8) as usize]
+    }
+
+    pub fn write(&mut self, register: u8, value: u8) {
+        self.update();
+        match register - RTCRegister::Seconds as u8 {
+            0 => self.seconds = value & 0x3F,
+            1 => self.minutes = value & 0x3F,
+            2 => self.hours = value & 0x1F,
+            3 => self.days = (self.days & 0x100) | value as u16,
+            _ => {
+                self.days = (self.days & 0xFF) | ((value as u16 & 1) << 8);
+                self.halted = value & HALT != 0;
+                self.carry = value & DAY_CARRY != 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cartridge::rtc::{RTC, RTC_SAVE_SIZE, RTCRegister, VirtualClock};
+
+    fn rtc() -> (RTC, VirtualClock) {
+        let clock = VirtualClock::new();
+        (RTC::new(Box::new(clock.clone())), clock)
+    }
+
+    #[test]
+    fn counts_time_between_latches() {
+        let (mut rtc, clock) = rtc();
+        clock.advance(2 * 86_400 + 3 * 3_600 + 4 * 60 + 5);
+        assert_eq!(rtc.read(RTCRegister::Seconds as u8), 0);
+
+        rtc.latch();
+        assert_eq!(rtc.read(RTCRegister::Seconds as u8), 5);
+        assert_eq!(rtc.read(RTCRegister::Minutes as u8), 4);
+        assert_eq!(rtc.read(RTCRegister::Hours as u8), 3);
+        assert_eq!(rtc.read(RTCRegister::DayLow as u8), 2);
+        assert_eq!(rtc.read(RTCRegister::DayHigh as u8), 0);
+    }
+
+    #[test]
+    fn day_counter_overflow_sets_carry() {
+        let (mut rtc, clock) = rtc();
+        rtc.write(RTCRegister::DayLow as u8, 0xFF);
+        rtc.write(RTCRegister::DayHigh as u8, 0x01);
+        clock.advance(86_400);
+
+        rtc.latch();
+        assert_eq!(rtc.read(RTCRegister::DayLow as u8), 0);
+        assert_eq!(rtc.read(RTCRegister::DayHigh as u8), 0x80);
+    }
+
+    #[test]
+    fn halt_stops_the_clock() {
+        let (mut rtc, clock) = rtc();
+        rtc.write(RTCRegister::DayHigh as u8, 0x40);
+        clock.advance(100);
+        rtc.write(RTCRegister::DayHigh as u8, 0x00);
+        clock.advance(10);
+
+        rtc.latch();
+        assert_eq!(rtc.read(RTCRegister::Seconds as u8), 10);
+    }
+
+    #[test]
+    fn save_keeps_counting_while_off() {
+        let (mut rtc, clock) = rtc();
+        clock.advance(1_000);
+        rtc.write(RTCRegister::Hours as u8, 5);
+        rtc.latch();
+        let block = rtc.save();
+        assert_eq!(block.len(), RTC_SAVE_SIZE);
+        assert_eq!(block[8], 5);
+        assert_eq!(block[40..48], 1_000u64.to_le_bytes());
+
+        clock.advance(60);
+        let mut loaded = RTC::new(Box::new(clock.clone()));
+        loaded.load(&block);
+        assert_eq!(loaded.read(RTCRegister::Hours as u8), 5);
+        // 1000 seconds before saving and 60 after
+        loaded.latch();
+        assert_eq!(loaded.read(RTCRegister::Minutes as u8), 17);
+        assert_eq!(loaded.read(RTCRegister::Seconds as u8), 40);
+    }
+}
@@ -2,12 +2,457 @@ use crate::hardware::cpu::CPU;
 use crate::hardware::cpu::registers::flags::Flag;
 
 impl CPU {
+    pub fn alu_add(&mut self, a: u8, b: u8) -> u8 {
+        let result = a.wrapping_add(b);
+        self.registers.set_flag(Flag::Zero, result == 0);
+        self.registers.set_flag(Flag::Negative, false);
+        self.registers.set_flag(Flag::HalfCarry, (a & 0x0F) + (b & 0x0F) > 0x0F);
+        self.registers.set_flag(Flag::Carry, (a as u16) + (b as u16) > 0xFF);
+        result
+    }
+
+    pub fn alu_adc(&mut self, a: u8, b: u8) -> u8 {
+        let carry = self.registers.get_flag(Flag::Carry) as u8;
+        let result = a.wrapping_add(b).wrapping_add(carry);
+        self.registers.set_flag(Flag::Zero, result == 0);
+        self.registers.set_flag(Flag::Negative, false);
+        self.registers.set_flag(Flag::HalfCarry, (a & 0x0F) + (b & 0x0F) + carry > 0x0F);
+        self.registers.set_flag(Flag::Carry, (a as u16) + (b as u16) + (carry as u16) > 0xFF);
+        result
+    }
+
     pub fn alu_sub(&mut self, a: u8, b: u8) -> u8 {
         let result = a.wrapping_sub(b);
         self.registers.set_flag(Flag::Zero, result == 0);
         self.registers.set_flag(Flag::Negative, true);
-        self.registers.set_flag(Flag::HalfCarry, (a & 0x0F) > (b & 0x0F));
-        self.registers.set_flag(Flag::Carry, a > b);
+        self.registers.set_flag(Flag::HalfCarry, (b & 0x0F) > (a & 0x0F));
+        self.registers.set_flag(Flag::Carry, b > a);
         result
     }
-}
\ No newline at end of file
+
+    pub fn alu_sbc(&mut self, a: u8, b: u8) -> u8 {
+        let carry = self.registers.get_flag(Flag::Carry) as u8;
+        let result = a.wrapping_sub(b).wrapping_sub(carry);
+        self.registers.set_flag(Flag::Zero, result == 0);
+        self.registers.set_flag(Flag::Negative, true);
+        self.registers.set_flag(Flag::HalfCarry, (b & 0x0F) + carry > (a & 0x0F));
+        self.registers.set_flag(Flag::Carry, (b as u16) + (carry as u16) > (a as u16));
+        result
+    }
+
+    pub fn alu_and(&mut self, a: u8, b: u8) -> u8 {
+        let result = a & b;
+        self.registers.set_flag(Flag::Zero, result == 0);
+        self.registers.set_flag(Flag::Negative, false);
+        self.registers.set_flag(Flag::HalfCarry, true);
+        self.registers.set_flag(Flag::Carry, false);
+        result
+    }
+
+    pub fn alu_xor(&mut self, a: u8, b: u8) -> u8 {
+        let result = a ^ b;
+        self.registers.set_flag(Flag::Zero, result == 0);
+        self.registers.set_flag(Flag::Negative, false);
+        self.registers.set_flag(Flag::HalfCarry, false);
+        self.registers.set_flag(Flag::Carry, false);
+        result
+    }
+
+    pub fn alu_or(&mut self, a: u8, b: u8) -> u8 {
+        let result = a | b;
+        self.registers.set_flag(Flag::Zero, result == 0);
+        self.registers.set_flag(Flag::Negative, false);
+        self.registers.set_flag(Flag::HalfCarry, false);
+        self.registers.set_flag(Flag::Carry, false);
+        result
+    }
+
+    pub fn alu_cp(&mut self, a: u8, b: u8) {
+        self.alu_sub(a, b);
+    }
+
+    pub fn alu_inc(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_add(1);
+        self.registers.set_flag(Flag::Zero, result == 0);
+        self.registers.set_flag(Flag::Negative, false);
+        self.registers.set_flag(Flag::HalfCarry, (value & 0x0F) == 0x0F);
+        result
+    }
+
+    pub fn alu_dec(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_sub(1);
+        self.registers.set_flag(Flag::Zero, result == 0);
+        self.registers.set_flag(Flag::Negative, true);
+        self.registers.set_flag(Flag::HalfCarry, (value & 0x0F) == 0x00);
+        result
+    }
+
+    pub fn alu_add_hl(&mut self, value: u16) {
+        let hl = self.registers.get_hl();
+        let result = hl.wrapping_add(value);
+        self.registers.set_flag(Flag::Negative, false);
+        self.registers.set_flag(Flag::HalfCarry, (hl & 0x0FFF) + (value & 0x0FFF) > 0x0FFF);
+        self.registers.set_flag(Flag::Carry, (hl as u32) + (value as u32) > 0xFFFF);
+        self.registers.set_hl(result);
+    }
+
+    // Shared by ADD SP, i8 and LD HL, SP+i8: the carries come from the low byte
+    pub fn alu_add_sp(&mut self, offset: i8) -> u16 {
+        let sp = self.registers.sp;
+        let value = offset as u16;
+        self.registers.set_flag(Flag::Zero, false);
+        self.registers.set_flag(Flag::Negative, false);
+        self.registers.set_flag(Flag::HalfCarry, (sp & 0x000F) + (value & 0x000F) > 0x000F);
+        self.registers.set_flag(Flag::Carry, (sp & 0x00FF) + (value & 0x00FF) > 0x00FF);
+        sp.wrapping_add(value)
+    }
+
+    pub fn alu_daa(&mut self) {
+        let mut a = self.registers.a;
+        let mut carry = self.registers.get_flag(Flag::Carry);
+        if self.registers.get_flag(Flag::Negative) {
+            if carry {
+                a = a.wrapping_sub(0x60);
+            }
+            if self.registers.get_flag(Flag::HalfCarry) {
+                a = a.wrapping_sub(0x06);
+            }
+        } else {
+            if carry || a > 0x99 {
+                a = a.wrapping_add(0x60);
+                carry = true;
+            }
+            if self.registers.get_flag(Flag::HalfCarry) || (a & 0x0F) > 0x09 {
+                a = a.wrapping_add(0x06);
+            }
+        }
+        self.registers.a = a;
+        self.registers.set_flag(Flag::Zero, a == 0);
+        self.registers.set_flag(Flag::HalfCarry, false);
+        self.registers.set_flag(Flag::Carry, carry);
+    }
+
+    pub fn alu_cpl(&mut self) {
+        self.registers.a = !self.registers.a;
+        self.registers.set_flag(Flag::Negative, true);
+        self.registers.set_flag(Flag::HalfCarry, true);
+    }
+
+    pub fn alu_scf(&mut self) {
+        self.registers.set_flag(Flag::Negative, false);
+        self.registers.set_flag(Flag::HalfCarry, false);
+        self.registers.set_flag(Flag::Carry, true);
+    }
+
+    pub fn alu_ccf(&mut self) {
+        let carry = self.registers.get_flag(Flag::Carry);
+        self.registers.set_flag(Flag::Negative, false);
+        self.registers.set_flag(Flag::HalfCarry, false);
+        self.registers.set_flag(Flag::Carry, !carry);
+    }
+
+    pub fn alu_rlc(&mut self, value: u8) -> u8 {
+        let result = value.rotate_left(1);
+        self.set_shift_flags(result, value & 0x80 != 0);
+        result
+    }
+
+    pub fn alu_rrc(&mut self, value: u8) -> u8 {
+        let result = value.rotate_right(1);
+        self.set_shift_flags(result, value & 0x01 != 0);
+        result
+    }
+
+    pub fn alu_rl(&mut self, value: u8) -> u8 {
+        let carry = self.registers.get_flag(Flag::Carry) as u8;
+        let result = (value << 1) | carry;
+        self.set_shift_flags(result, value & 0x80 != 0);
+        result
+    }
+
+    pub fn alu_rr(&mut self, value: u8) -> u8 {
+        let carry = self.registers.get_flag(Flag::Carry) as u8;
+        let result = (value >> 1) | (carry << 7);
+        self.set_shift_flags(result, value & 0x01 != 0);
+        result
+    }
+
+    pub fn alu_sla(&mut self, value: u8) -> u8 {
+        let result = value << 1;
+        self.set_shift_flags(result, value & 0x80 != 0);
+        result
+    }
+
+    // arithmetic shift keeps the sign bit
+    pub fn alu_sra(&mut self, value: u8) -> u8 {
+        let result = (value >> 1) | (value & 0x80);
+        self.set_shift_flags(result, value & 0x01 != 0);
+        result
+    }
+
+    pub fn alu_srl(&mut self, value: u8) -> u8 {
+        let result = value >> 1;
+        self.set_shift_flags(result, value & 0x01 != 0);
+        result
+    }
+
+    pub fn alu_swap(&mut self, value: u8) -> u8 {
+        let result = value.rotate_left(4);
+        self.set_shift_flags(result, false);
+        result
+    }
+
+    pub fn alu_bit(&mut self, bit: u8, value: u8) {
+        self.registers.set_flag(Flag::Zero, value & (1 << bit) == 0);
+        self.registers.set_flag(Flag::Negative, false);
+        self.registers.set_flag(Flag::HalfCarry, true);
+    }
+
+    fn set_shift_flags(&mut self, result: u8, carry: bool) {
+        self.registers.set_flag(Flag::Zero, result == 0);
+        self.registers.set_flag(Flag::Negative, false);
+        self.registers.set_flag(Flag::HalfCarry, false);
+        self.registers.set_flag(Flag::Carry, carry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cpu::CPU;
+
+    // F values: Z = 0x80, N = 0x40, H = 0x20, C = 0x10
+    const Z: u8 = 0x80;
+    const N: u8 = 0x40;
+    const H: u8 = 0x20;
+    const C: u8 = 0x10;
+
+    fn cpu_with_flags(f: u8) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.registers.f = f;
+        cpu
+    }
+
+    // (a, b, flags before, result, flags after)
+    fn check(op: fn(&mut CPU, u8, u8) -> u8, cases: &[(u8, u8, u8, u8, u8)]) {
+        for &(a, b, before, result, after) in cases {
+            let mut cpu = cpu_with_flags(before);
+            assert_eq!(op(&mut cpu, a, b), result, "result of {:02X}, {:02X} with F={:02X}", a, b, before);
+            assert_eq!(cpu.registers.f, after, "flags of {:02X}, {:02X} with F={:02X}", a, b, before);
+        }
+    }
+
+    #[test]
+    fn add() {
+        check(CPU::alu_add, &[
+            (0x01, 0x02, 0, 0x03, 0),
+            (0x00, 0x00, N | C, 0x00, Z),
+            (0x0F, 0x01, 0, 0x10, H),
+            (0xF0, 0x20, 0, 0x10, C),
+            (0x80, 0x80, 0, 0x00, Z | C),
+            (0xFF, 0x01, 0, 0x00, Z | H | C),
+            (0x8F, 0x01, Z | N | H | C, 0x90, H),
+        ]);
+    }
+
+    #[test]
+    fn adc() {
+        check(CPU::alu_adc, &[
+            (0x01, 0x02, 0, 0x03, 0),
+            (0x01, 0x02, C, 0x04, 0),
+            (0x0E, 0x01, C, 0x10, H),
+            (0xFE, 0x01, C, 0x00, Z | H | C),
+            (0xF0, 0x0F, C, 0x00, Z | H | C),
+            (0x80, 0x80, 0, 0x00, Z | C),
+            (0x00, 0x00, Z | N | H, 0x00, Z),
+        ]);
+    }
+
+    #[test]
+    fn sub() {
+        check(CPU::alu_sub, &[
+            (0x03, 0x02, 0, 0x01, N),
+            (0x02, 0x02, 0, 0x00, Z | N),
+            (0x10, 0x01, 0, 0x0F, N | H),
+            (0x01, 0x02, 0, 0xFF, N | H | C),
+            (0x20, 0x30, 0, 0xF0, N | C),
+            (0x3E, 0x0F, Z | C, 0x2F, N | H),
+        ]);
+    }
+
+    #[test]
+    fn sbc() {
+        check(CPU::alu_sbc, &[
+            (0x03, 0x02, 0, 0x01, N),
+            (0x03, 0x02, C, 0x00, Z | N),
+            (0x10, 0x00, C, 0x0F, N | H),
+            (0x00, 0x00, C, 0xFF, N | H | C),
+            (0x00, 0xFF, C, 0x00, Z | N | H | C),
+            (0x20, 0x10, C, 0x0F, N | H),
+            (0x20, 0x30, 0, 0xF0, N | C),
+        ]);
+    }
+
+    #[test]
+    fn logic() {
+        check(CPU::alu_and, &[
+            (0xF0, 0x0F, 0, 0x00, Z | H),
+            (0xFF, 0x3C, N | C, 0x3C, H),
+        ]);
+        check(CPU::alu_xor, &[
+            (0xAA, 0xAA, N | H | C, 0x00, Z),
+            (0xF0, 0x0F, 0, 0xFF, 0),
+        ]);
+        check(CPU::alu_or, &[
+            (0x00, 0x00, N | H | C, 0x00, Z),
+            (0xF0, 0x0F, 0, 0xFF, 0),
+        ]);
+    }
+
+    #[test]
+    fn cp_leaves_a_alone() {
+        let mut cpu = cpu_with_flags(0);
+        cpu.registers.a = 0x10;
+        cpu.alu_cp(cpu.registers.a, 0x20);
+        assert_eq!(cpu.registers.a, 0x10);
+        assert_eq!(cpu.registers.f, N | C);
+    }
+
+    #[test]
+    fn inc_and_dec_leave_carry() {
+        // (value, flags before, result, flags after)
+        let inc = [
+            (0x00, 0, 0x01, 0),
+            (0x0F, C, 0x10, H | C),
+            (0xFF, N, 0x00, Z | H),
+            (0xFF, C, 0x00, Z | H | C),
+        ];
+        for (value, before, result, after) in inc {
+            let mut cpu = cpu_with_flags(before);
+            assert_eq!(cpu.alu_inc(value), result);
+            assert_eq!(cpu.registers.f, after);
+        }
+
+        let dec = [
+            (0x02, 0, 0x01, N),
+            (0x01, C, 0x00, Z | N | C),
+            (0x10, 0, 0x0F, N | H),
+            (0x00, C, 0xFF, N | H | C),
+        ];
+        for (value, before, result, after) in dec {
+            let mut cpu = cpu_with_flags(before);
+            assert_eq!(cpu.alu_dec(value), result);
+            assert_eq!(cpu.registers.f, after);
+        }
+    }
+
+    #[test]
+    fn add_hl_leaves_zero() {
+        // (hl, value, flags before, result, flags after)
+        let cases = [
+            (0x0001, 0x0001, N, 0x0002, 0),
+            (0x0FFF, 0x0001, Z, 0x1000, Z | H),
+            (0xF000, 0x1000, 0, 0x0000, C),
+            (0xFFFF, 0x0001, Z | N, 0x0000, Z | H | C),
+            (0x00FF, 0x0001, 0, 0x0100, 0),
+        ];
+        for (hl, value, before, result, after) in cases {
+            let mut cpu = cpu_with_flags(before);
+            cpu.registers.set_hl(hl);
+            cpu.alu_add_hl(value);
+            assert_eq!(cpu.registers.get_hl(), result);
+            assert_eq!(cpu.registers.f, after);
+        }
+    }
+
+    #[test]
+    fn add_sp_uses_low_byte_carries() {
+        // (sp, offset, result, flags after)
+        let cases = [
+            (0x0000, 0x01, 0x0001, 0),
+            (0x000F, 0x01, 0x0010, H),
+            (0x00F0, 0x10, 0x0100, C),
+            (0x00FF, 0x01, 0x0100, H | C),
+            (0x0005, -1, 0x0004, H | C),
+            (0x0000, -1, 0xFFFF, 0),
+            (0xFFFF, 0x01, 0x0000, H | C),
+        ];
+        for (sp, offset, result, after) in cases {
+            let mut cpu = cpu_with_flags(Z | N);
+            cpu.registers.sp = sp;
+            assert_eq!(cpu.alu_add_sp(offset), result);
+            assert_eq!(cpu.registers.f, after, "SP={:04X} offset={}", sp, offset);
+        }
+    }
+
+    #[test]
+    fn daa() {
+        // (a, flags before, result, flags after)
+        let cases = [
+            (0x00, 0, 0x00, Z),
+            (0x0A, 0, 0x10, 0),
+            (0x9A, 0, 0x00, Z | C),
+            (0xA0, 0, 0x00, Z | C),
+            (0x12, H, 0x18, 0),
+            (0x15, C, 0x75, C),
+            (0x45, N, 0x45, N),
+            (0x0F, N | H, 0x09, N),
+            (0xA0, N | C, 0x40, N | C),
+            (0xFF, N | H | C, 0x99, N | C),
+        ];
+        for (a, before, result, after) in cases {
+            let mut cpu = cpu_with_flags(before);
+            cpu.registers.a = a;
+            cpu.alu_daa();
+            assert_eq!(cpu.registers.a, result, "A={:02X} F={:02X}", a, before);
+            assert_eq!(cpu.registers.f, after, "A={:02X} F={:02X}", a, before);
+        }
+    }
+
+    #[test]
+    fn carry_flag_ops() {
+        let mut cpu = cpu_with_flags(Z | N | H);
+        cpu.alu_scf();
+        assert_eq!(cpu.registers.f, Z | C);
+        cpu.alu_ccf();
+        assert_eq!(cpu.registers.f, Z);
+
+        let mut cpu = cpu_with_flags(C);
+        cpu.registers.a = 0x35;
+        cpu.alu_cpl();
+        assert_eq!(cpu.registers.a, 0xCA);
+        assert_eq!(cpu.registers.f, N | H | C);
+    }
+
+    type Shift = fn(&mut CPU, u8) -> u8;
+
+    #[test]
+    fn rotates_and_shifts() {
+        // (value, flags before, result, flags after)
+        let rl: [(Shift, u8, u8, u8, u8); 10] = [
+            (CPU::alu_rlc, 0x85, 0, 0x0B, C),
+            (CPU::alu_rrc, 0x01, 0, 0x80, C),
+            (CPU::alu_rl, 0x80, 0, 0x00, Z | C),
+            (CPU::alu_rl, 0x00, C, 0x01, 0),
+            (CPU::alu_rr, 0x01, 0, 0x00, Z | C),
+            (CPU::alu_rr, 0x00, C, 0x80, 0),
+            (CPU::alu_sla, 0xFF, 0, 0xFE, C),
+            (CPU::alu_sra, 0x81, 0, 0xC0, C),
+            (CPU::alu_srl, 0x01, 0, 0x00, Z | C),
+            (CPU::alu_swap, 0xF0, N | H | C, 0x0F, 0),
+        ];
+        for (op, value, before, result, after) in rl {
+            let mut cpu = cpu_with_flags(before);
+            assert_eq!(op(&mut cpu, value), result);
+            assert_eq!(cpu.registers.f, after, "value {:02X}", value);
+        }
+    }
+
+    #[test]
+    fn bit_keeps_carry() {
+        let mut cpu = cpu_with_flags(N | C);
+        cpu.alu_bit(7, 0x7F);
+        assert_eq!(cpu.registers.f, Z | H | C);
+        cpu.alu_bit(0, 0x01);
+        assert_eq!(cpu.registers.f, H | C);
+    }
+}
@@ -1,20 +1,334 @@
 use std::ops::RangeInclusive;
-use crate::hardware::cpu::CPU;
+use crate::hardware::apu::{APU, NR52};
+use crate::hardware::boot_rom::{BOOT, BootROM};
+use crate::hardware::cartridge::Cartridge;
+use crate::hardware::cpu::interrupts::{INTERRUPT_ENABLE, INTERRUPT_FLAG, Interrupt, Interrupts};
+use crate::hardware::cpu::memory::Memory;
+use crate::hardware::joypad::{Joypad, P1};
+use crate::hardware::model::Model;
+use crate::hardware::ppu::{BGP, LCDC, OAM_SIZE, PPU};
+use crate::hardware::serial::{SB, SC, Serial};
+use crate::hardware::timer::Timer;
 
 const FIXED_ROM_BANK: RangeInclusive<u16> = 0x0000..=0x3FFF;
+const SWITCHABLE_ROM_BANK: RangeInclusive<u16> = 0x4000..=0x7FFF;
+const VRAM: RangeInclusive<u16> = 0x8000..=0x9FFF;
+const EXTERNAL_RAM: RangeInclusive<u16> = 0xA000..=0xBFFF;
+const WRAM: RangeInclusive<u16> = 0xC000..=0xDFFF;
+// mirrors 0xC000-0xDDFF
+const ECHO_RAM: RangeInclusive<u16> = 0xE000..=0xFDFF;
+const OAM: RangeInclusive<u16> = 0xFE00..=0xFE9F;
+const UNUSABLE: RangeInclusive<u16> = 0xFEA0..=0xFEFF;
+const IO_REGISTERS: RangeInclusive<u16> = 0xFF00..=0xFF7F;
+const TIMER_REGISTERS: RangeInclusive<u16> = 0xFF04..=0xFF07;
+const APU_REGISTERS: RangeInclusive<u16> = 0xFF10..=0xFF3F;
+const PPU_REGISTERS: RangeInclusive<u16> = 0xFF40..=0xFF4B;
+const DMA: u16 = 0xFF46;
+const HRAM: RangeInclusive<u16> = 0xFF80..=0xFFFE;
 
-impl CPU {
-    pub fn bus_read(&self, address: u16) -> u8 {
-        if FIXED_ROM_BANK.contains(&address){
-            return self.cartridge.read(address);
+// what the CPU is wired to, the whole console or just memory in tests and tools that reuse the core
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+    // called once for every M-cycle the CPU spends, whether or not it accesses the bus
+    fn tick(&mut self) {}
+
+    // interrupt lines the CPU checks between instructions, by default IE and IF in the address space
+    fn interrupt_enable(&mut self) -> u8 {
+        self.read(INTERRUPT_ENABLE)
+    }
+
+    fn interrupt_flag(&mut self) -> u8 {
+        self.read(INTERRUPT_FLAG)
+    }
+
+    fn pending_interrupts(&mut self) -> u8 {
+        self.interrupt_enable() & self.interrupt_flag() & 0x1F
+    }
+
+    fn acknowledge_interrupt(&mut self, interrupt: Interrupt) {
+        let flag = self.interrupt_flag();
+        self.write(INTERRUPT_FLAG, flag & !(interrupt as u8));
+    }
+
+    // STOP resets the divider
+    fn reset_divider(&mut self) {}
+
+    // P10-P13, low for a pressed button in a selected row, STOP waits for one to go low
+    fn joypad_lines(&mut self) -> u8 {
+        self.read(P1) & 0x0F
+    }
+}
+
+// the console's own bus, everything the CPU reaches through it
+pub struct SystemBus {
+    pub memory: Memory,
+    pub cartridge: Cartridge,
+    // mapped over the cartridge until 0xFF50 is written
+    pub boot_rom: Option<BootROM>,
+    pub ppu: PPU,
+    pub timer: Timer,
+    pub apu: APU,
+    pub joypad: Joypad,
+    pub serial: Serial,
+    pub interrupts: Interrupts,
+}
+
+impl SystemBus {
+    pub fn new(cartridge: Cartridge) -> Self {
+        SystemBus {
+            memory: Memory::new(),
+            cartridge,
+            boot_rom: None,
+            ppu: PPU::new(),
+            timer: Timer::new(),
+            apu: APU::new(),
+            joypad: Joypad::new(),
+            serial: Serial::new(),
+            interrupts: Interrupts::new(),
+        }
+    }
+
+    // the I/O state the model's boot ROM leaves when it hands over to the cartridge
+    pub fn with_model(cartridge: Cartridge, model: Model) -> Self {
+        let mut bus = SystemBus::new(cartridge);
+        bus.timer.set_counter(model.divider());
+        for &(address, value) in model.io_registers() {
+            Bus::write(&mut bus, address, value);
+        }
+        // the boot ROM ends during VBlank, which leaves the interrupt requested
+        bus.interrupts.request(Interrupt::VBlank);
+        bus
+    }
+
+    // power on, with the boot ROM mapped over the cartridge to do the setup
+    pub fn with_boot_rom(cartridge: Cartridge, boot_rom: BootROM) -> Self {
+        let mut bus = SystemBus::new(cartridge);
+        bus.boot_rom = Some(boot_rom);
+        // the LCD and the APU start off and the palettes cleared
+        bus.ppu.write_register(LCDC, 0x00, &mut bus.interrupts);
+        bus.ppu.write_register(BGP, 0x00, &mut bus.interrupts);
+        bus.apu.write(NR52, 0x00);
+        bus
+    }
+
+    // reads without side effects, so it only needs a shared reference
+    pub fn read(&self, address: u16) -> u8 {
+        if let Some(boot_rom) = self.boot_rom.as_ref().filter(|boot_rom| boot_rom.maps(address)) {
+            return boot_rom.read(address);
+        }
+        match address {
+            _ if FIXED_ROM_BANK.contains(&address) || SWITCHABLE_ROM_BANK.contains(&address) => {
+                self.cartridge.read(address)
+            }
+            _ if VRAM.contains(&address) => self.ppu.read_vram(address),
+            _ if EXTERNAL_RAM.contains(&address) => self.cartridge.read_ram(address),
+            _ if WRAM.contains(&address) => self.memory.wram[(address - WRAM.start()) as usize],
+            _ if ECHO_RAM.contains(&address) => self.memory.wram[(address - ECHO_RAM.start()) as usize],
+            _ if OAM.contains(&address) => self.ppu.read_oam(address),
+            // DMG models read zeros here, unless the PPU is blocking OAM
+            _ if UNUSABLE.contains(&address) => if self.ppu.is_oam_accessible() { 0x00 } else { 0xFF },
+            P1 => self.joypad.read(),
+            SB | SC => self.serial.read(address),
+            _ if TIMER_REGISTERS.contains(&address) => self.timer.read(address),
+            INTERRUPT_FLAG => self.interrupts.read_flag(),
+            _ if APU_REGISTERS.contains(&address) => self.apu.read(address),
+            DMA => self.memory.io[(address - IO_REGISTERS.start()) as usize],
+            BOOT => 0xFF,
+            _ if PPU_REGISTERS.contains(&address) => self.ppu.read_register(address),
+            _ if IO_REGISTERS.contains(&address) => self.memory.io[(address - IO_REGISTERS.start()) as usize],
+            _ if HRAM.contains(&address) => self.memory.hram[(address - HRAM.start()) as usize],
+            INTERRUPT_ENABLE => self.interrupts.enable,
+            _ => unreachable!("0x{:04X} is outside the address space", address),
         }
+    }
+
+    // copies 160 bytes from 0xXX00 into OAM, done in one go rather than over 160 M-cycles
+    fn oam_dma(&mut self, source: u8) {
+        let start = (source as u16) << 8;
+        for offset in 0..OAM_SIZE as u16 {
+            self.ppu.oam[offset as usize] = self.read(start + offset);
+        }
+    }
+}
 
-        unimplemented!("bus read");
+impl Bus for SystemBus {
+    fn read(&mut self, address: u16) -> u8 {
+        SystemBus::read(self, address)
     }
 
-    pub fn bus_write(&mut self, address: u16, value: u8) {
-        if FIXED_ROM_BANK.contains(&address){
-            self.cartridge.write(address, value);
+    fn write(&mut self, address: u16, value: u8) {
+        match address {
+            // the cartridge decides what a write to ROM means, usually a bank switch
+            _ if FIXED_ROM_BANK.contains(&address) || SWITCHABLE_ROM_BANK.contains(&address) => {
+                self.cartridge.write(address, value)
+            }
+            _ if VRAM.contains(&address) => self.ppu.write_vram(address, value),
+            _ if EXTERNAL_RAM.contains(&address) => self.cartridge.write_ram(address, value),
+            _ if WRAM.contains(&address) => self.memory.wram[(address - WRAM.start()) as usize] = value,
+            _ if ECHO_RAM.contains(&address) => self.memory.wram[(address - ECHO_RAM.start()) as usize] = value,
+            _ if OAM.contains(&address) => self.ppu.write_oam(address, value),
+            _ if UNUSABLE.contains(&address) => {}
+            P1 => self.joypad.write(value, &mut self.interrupts),
+            SB | SC => self.serial.write(address, value),
+            _ if TIMER_REGISTERS.contains(&address) => self.timer.write(address, value),
+            INTERRUPT_FLAG => self.interrupts.write_flag(value),
+            _ if APU_REGISTERS.contains(&address) => self.apu.write(address, value),
+            DMA => {
+                self.memory.io[(address - IO_REGISTERS.start()) as usize] = value;
+                self.oam_dma(value);
+            }
+            _ if PPU_REGISTERS.contains(&address) => self.ppu.write_register(address, value, &mut self.interrupts),
+            // there is no way back once the boot ROM is unmapped
+            BOOT => {
+                if value & 1 != 0 {
+                    self.boot_rom = None;
+                }
+            }
+            _ if IO_REGISTERS.contains(&address) => self.memory.io[(address - IO_REGISTERS.start()) as usize] = value,
+            _ if HRAM.contains(&address) => self.memory.hram[(address - HRAM.start()) as usize] = value,
+            INTERRUPT_ENABLE => self.interrupts.enable = value,
+            _ => unreachable!("0x{:04X} is outside the address space", address),
         }
     }
-}
\ No newline at end of file
+
+    // one M-cycle for every peripheral, they share the timer's counter
+    fn tick(&mut self) {
+        self.timer.tick(&mut self.interrupts);
+        self.apu.tick(self.timer.counter());
+        self.serial.tick(self.timer.counter(), &mut self.interrupts);
+        self.ppu.tick(&mut self.interrupts);
+    }
+
+    fn interrupt_enable(&mut self) -> u8 {
+        self.interrupts.enable
+    }
+
+    fn interrupt_flag(&mut self) -> u8 {
+        self.interrupts.flag
+    }
+
+    fn pending_interrupts(&mut self) -> u8 {
+        self.interrupts.pending()
+    }
+
+    fn acknowledge_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupts.acknowledge(interrupt);
+    }
+
+    fn reset_divider(&mut self) {
+        self.timer.reset_divider();
+    }
+
+    fn joypad_lines(&mut self) -> u8 {
+        self.joypad.read() & 0x0F
+    }
+}
+
+// 64 KiB of plain RAM with nothing attached, IE and IF are just bytes in it
+pub struct FlatBus {
+    pub memory: Vec<u8>,
+}
+
+impl FlatBus {
+    pub fn new() -> Self {
+        FlatBus { memory: vec![0; 0x10000] }
+    }
+
+    // a bus with program copied in at address
+    pub fn with_program(address: u16, program: &[u8]) -> Self {
+        let mut bus = FlatBus::new();
+        bus.memory[address as usize..address as usize + program.len()].copy_from_slice(program);
+        bus
+    }
+}
+
+impl Default for FlatBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bus for FlatBus {
+    fn read(&mut self, address: u16) -> u8 {
+        self.memory[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.memory[address as usize] = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cartridge::Cartridge;
+    use crate::hardware::cpu::bus::{Bus, FlatBus, SystemBus};
+    use crate::hardware::cpu::CPU;
+    use crate::hardware::cpu::interrupts::{INTERRUPT_ENABLE, INTERRUPT_FLAG, Interrupt};
+
+    #[test]
+    fn echo_ram_mirrors_wram() {
+        let mut bus = SystemBus::new(Cartridge::blank());
+        bus.write(0xC123, 0x42);
+        assert_eq!(bus.read(0xE123), 0x42);
+        bus.write(0xFDFF, 0x24);
+        assert_eq!(bus.read(0xDDFF), 0x24);
+    }
+
+    #[test]
+    fn rom_writes_do_not_change_rom() {
+        let mut bus = SystemBus::new(Cartridge::with_program(0x0000, &[0x3C]));
+        bus.write(0x0000, 0x00);
+        assert_eq!(bus.read(0x0000), 0x3C);
+    }
+
+    #[test]
+    fn unusable_region_reads_zero_and_ignores_writes() {
+        let mut bus = SystemBus::new(Cartridge::blank());
+        // while the PPU is scanning OAM the region reads 0xFF
+        assert_eq!(bus.read(0xFEA0), 0xFF);
+
+        bus.write(0xFF40, 0x00);
+        bus.write(0xFEA0, 0x42);
+        assert_eq!(bus.read(0xFEA0), 0x00);
+        assert_eq!(bus.read(0xFEFF), 0x00);
+    }
+
+    #[test]
+    fn high_ram_and_interrupt_enable_are_separate() {
+        let mut bus = SystemBus::new(Cartridge::blank());
+        bus.write(0xFFFE, 0x12);
+        bus.write(0xFFFF, 0x1F);
+        assert_eq!(bus.read(0xFFFE), 0x12);
+        assert_eq!(bus.read(0xFFFF), 0x1F);
+        assert_eq!(bus.interrupts.enable, 0x1F);
+    }
+
+    #[test]
+    fn cpu_runs_against_flat_memory() {
+        // LD HL, 0x8000; LD A, 0x42; LD (HL), A
+        let mut bus = FlatBus::with_program(0x0100, &[0x21, 0x00, 0x80, 0x3E, 0x42, 0x77]);
+        let mut cpu = CPU::new();
+        for _ in 0..3 {
+            cpu.step(&mut bus);
+        }
+        // plain RAM even where the console has ROM or VRAM
+        assert_eq!(bus.memory[0x8000], 0x42);
+        bus.write(0x0000, 0x24);
+        assert_eq!(bus.read(0x0000), 0x24);
+    }
+
+    #[test]
+    fn flat_bus_interrupts_live_in_memory() {
+        let mut bus = FlatBus::new();
+        let mut cpu = CPU::new();
+        cpu.ime = true;
+        cpu.registers.sp = 0xD000;
+        bus.memory[INTERRUPT_ENABLE as usize] = Interrupt::Timer as u8;
+        bus.memory[INTERRUPT_FLAG as usize] = Interrupt::Timer as u8 | Interrupt::VBlank as u8;
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(cpu.registers.pc, Interrupt::Timer.vector());
+        assert_eq!(bus.memory[INTERRUPT_FLAG as usize], Interrupt::VBlank as u8);
+    }
+}
@@ -1,9 +1,9 @@
 use std::fmt;
 use std::fmt::Formatter;
 
-use crate::hardware::cpu::instructions::Target::{A, B, BC, C, D, DE, E, H, HL, I8, L, SP, U16, U8};
+use crate::hardware::cpu::instructions::Target::{A, AF, B, BC, C, D, DE, E, H, HighC, HighU8, HL, I8, IndirectBC, IndirectDE, IndirectHL, IndirectU16, L, SP, SPI8, U16, U8};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Target {
     A,
     B,
@@ -12,10 +12,7 @@ pub enum Target {
     E,
     H,
     L,
-    // Z,
-    // NZ,
-    // NC,
-    // C,
+    AF,
     BC,
     DE,
     HL,
@@ -23,37 +20,86 @@ pub enum Target {
     U8,
     U16,
     I8,
+    // (BC)
+    IndirectBC,
+    // (DE)
+    IndirectDE,
+    // (HL)
+    IndirectHL,
+    // (u16)
+    IndirectU16,
+    // (0xFF00 + C)
+    HighC,
+    // (0xFF00 + u8)
+    HighU8,
+    // SP + i8
+    SPI8,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum JRTarget {
-    N,
     Z,
     NZ,
     C,
     NC,
+    // unconditional
     I8,
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Instruction {
-    UNKNOWN(u8),
+    PREFIX,
+    ILLEGAL(u8),
     NOP,
     STOP,
+    HALT,
     ADD(Target, Target),
+    ADC(Target, Target),
+    SUB(Target, Target),
+    SBC(Target, Target),
+    AND(Target, Target),
+    XOR(Target, Target),
+    OR(Target, Target),
+    CP(Target, Target),
     INC(Target),
+    DEC(Target),
     LD(Target, Target),
     LDI(Target, Target),
     LDD(Target, Target),
-    DEC(Target),
+    PUSH(Target),
+    POP(Target),
     JP(Target),
-    CP(Target, Target),
+    JPCC(JRTarget),
     JR(JRTarget),
-    XOR(Target, Target),
+    CALL,
+    CALLCC(JRTarget),
+    RET,
+    RETCC(JRTarget),
+    RETI,
+    RST(u8),
     RLCA,
     RRCA,
+    RLA,
+    RRA,
+    DAA,
+    CPL,
+    SCF,
+    CCF,
     DI,
+    EI,
+    // CB-prefixed
+    RLC(Target),
+    RRC(Target),
+    RL(Target),
+    RR(Target),
+    SLA(Target),
+    SRA(Target),
+    SWAP(Target),
+    SRL(Target),
+    BIT(u8, Target),
+    RES(u8, Target),
+    SET(u8, Target),
 }
 
 impl Instruction {
@@ -61,36 +107,427 @@ impl Instruction {
         match byte {
             0x00 => Instruction::NOP,
             0x01 => Instruction::LD(BC, U16),
-
-            0x02 => Instruction::LD(BC, A),
+            0x02 => Instruction::LD(IndirectBC, A),
             0x03 => Instruction::INC(BC),
             0x04 => Instruction::INC(B),
             0x05 => Instruction::DEC(B),
             0x06 => Instruction::LD(B, U8),
             0x07 => Instruction::RLCA,
-            0x08 => Instruction::LD(U16, SP),
+            0x08 => Instruction::LD(IndirectU16, SP),
             0x09 => Instruction::ADD(HL, BC),
-            0x0A => Instruction::LD(A, BC),
+            0x0A => Instruction::LD(A, IndirectBC),
             0x0B => Instruction::DEC(BC),
             0x0C => Instruction::INC(C),
             0x0D => Instruction::DEC(C),
             0x0E => Instruction::LD(C, U8),
             0x0F => Instruction::RRCA,
+
             0x10 => Instruction::STOP,
-            0xC3 => Instruction::JP(U16),
-            0xFE => Instruction::CP(A, U8),
-            0x28 => Instruction::JR(JRTarget::Z),
-            0xAF => Instruction::XOR(A, A),
+            0x11 => Instruction::LD(DE, U16),
+            0x12 => Instruction::LD(IndirectDE, A),
+            0x13 => Instruction::INC(DE),
+            0x14 => Instruction::INC(D),
+            0x15 => Instruction::DEC(D),
+            0x16 => Instruction::LD(D, U8),
+            0x17 => Instruction::RLA,
             0x18 => Instruction::JR(JRTarget::I8),
-            0xEA => Instruction::LD(U16, A),
-            0x21 => Instruction::LD(HL, U16),
-            0x32 => Instruction::LDD(HL, A),
+            0x19 => Instruction::ADD(HL, DE),
+            0x1A => Instruction::LD(A, IndirectDE),
+            0x1B => Instruction::DEC(DE),
+            0x1C => Instruction::INC(E),
+            0x1D => Instruction::DEC(E),
+            0x1E => Instruction::LD(E, U8),
+            0x1F => Instruction::RRA,
+
             0x20 => Instruction::JR(JRTarget::NZ),
+            0x21 => Instruction::LD(HL, U16),
+            0x22 => Instruction::LDI(IndirectHL, A),
+            0x23 => Instruction::INC(HL),
+            0x24 => Instruction::INC(H),
+            0x25 => Instruction::DEC(H),
+            0x26 => Instruction::LD(H, U8),
+            0x27 => Instruction::DAA,
+            0x28 => Instruction::JR(JRTarget::Z),
+            0x29 => Instruction::ADD(HL, HL),
+            0x2A => Instruction::LDI(A, IndirectHL),
+            0x2B => Instruction::DEC(HL),
+            0x2C => Instruction::INC(L),
+            0x2D => Instruction::DEC(L),
+            0x2E => Instruction::LD(L, U8),
+            0x2F => Instruction::CPL,
+
+            0x30 => Instruction::JR(JRTarget::NC),
+            0x31 => Instruction::LD(SP, U16),
+            0x32 => Instruction::LDD(IndirectHL, A),
+            0x33 => Instruction::INC(SP),
+            0x34 => Instruction::INC(IndirectHL),
+            0x35 => Instruction::DEC(IndirectHL),
+            0x36 => Instruction::LD(IndirectHL, U8),
+            0x37 => Instruction::SCF,
+            0x38 => Instruction::JR(JRTarget::C),
+            0x39 => Instruction::ADD(HL, SP),
+            0x3A => Instruction::LDD(A, IndirectHL),
+            0x3B => Instruction::DEC(SP),
+            0x3C => Instruction::INC(A),
+            0x3D => Instruction::DEC(A),
             0x3E => Instruction::LD(A, U8),
+            0x3F => Instruction::CCF,
+
+            0x40 => Instruction::LD(B, B),
+            0x41 => Instruction::LD(B, C),
+            0x42 => Instruction::LD(B, D),
+            0x43 => Instruction::LD(B, E),
+            0x44 => Instruction::LD(B, H),
+            0x45 => Instruction::LD(B, L),
+            0x46 => Instruction::LD(B, IndirectHL),
+            0x47 => Instruction::LD(B, A),
+            0x48 => Instruction::LD(C, B),
+            0x49 => Instruction::LD(C, C),
+            0x4A => Instruction::LD(C, D),
+            0x4B => Instruction::LD(C, E),
+            0x4C => Instruction::LD(C, H),
+            0x4D => Instruction::LD(C, L),
+            0x4E => Instruction::LD(C, IndirectHL),
+            0x4F => Instruction::LD(C, A),
+
+            0x50 => Instruction::LD(D, B),
+            0x51 => Instruction::LD(D, C),
+            0x52 => Instruction::LD(D, D),
+            0x53 => Instruction::LD(D, E),
+            0x54 => Instruction::LD(D, H),
+            0x55 => Instruction::LD(D, L),
+            0x56 => Instruction::LD(D, IndirectHL),
+            0x57 => Instruction::LD(D, A),
+            0x58 => Instruction::LD(E, B),
+            0x59 => Instruction::LD(E, C),
+            0x5A => Instruction::LD(E, D),
+            0x5B => Instruction::LD(E, E),
+            0x5C => Instruction::LD(E, H),
+            0x5D => Instruction::LD(E, L),
+            0x5E => Instruction::LD(E, IndirectHL),
+            0x5F => Instruction::LD(E, A),
+
+            0x60 => Instruction::LD(H, B),
+            0x61 => Instruction::LD(H, C),
+            0x62 => Instruction::LD(H, D),
+            0x63 => Instruction::LD(H, E),
+            0x64 => Instruction::LD(H, H),
+            0x65 => Instruction::LD(H, L),
+            0x66 => Instruction::LD(H, IndirectHL),
+            0x67 => Instruction::LD(H, A),
+            0x68 => Instruction::LD(L, B),
+            0x69 => Instruction::LD(L, C),
+            0x6A => Instruction::LD(L, D),
+            0x6B => Instruction::LD(L, E),
+            0x6C => Instruction::LD(L, H),
+            0x6D => Instruction::LD(L, L),
+            0x6E => Instruction::LD(L, IndirectHL),
+            0x6F => Instruction::LD(L, A),
+
+            0x70 => Instruction::LD(IndirectHL, B),
+            0x71 => Instruction::LD(IndirectHL, C),
+            0x72 => Instruction::LD(IndirectHL, D),
+            0x73 => Instruction::LD(IndirectHL, E),
+            0x74 => Instruction::LD(IndirectHL, H),
+            0x75 => Instruction::LD(IndirectHL, L),
+            0x76 => Instruction::HALT,
+            0x77 => Instruction::LD(IndirectHL, A),
+            0x78 => Instruction::LD(A, B),
+            0x79 => Instruction::LD(A, C),
+            0x7A => Instruction::LD(A, D),
+            0x7B => Instruction::LD(A, E),
+            0x7C => Instruction::LD(A, H),
+            0x7D => Instruction::LD(A, L),
+            0x7E => Instruction::LD(A, IndirectHL),
+            0x7F => Instruction::LD(A, A),
+
+            0x80 => Instruction::ADD(A, B),
+            0x81 => Instruction::ADD(A, C),
+            0x82 => Instruction::ADD(A, D),
+            0x83 => Instruction::ADD(A, E),
+            0x84 => Instruction::ADD(A, H),
+            0x85 => Instruction::ADD(A, L),
+            0x86 => Instruction::ADD(A, IndirectHL),
+            0x87 => Instruction::ADD(A, A),
+            0x88 => Instruction::ADC(A, B),
+            0x89 => Instruction::ADC(A, C),
+            0x8A => Instruction::ADC(A, D),
+            0x8B => Instruction::ADC(A, E),
+            0x8C => Instruction::ADC(A, H),
+            0x8D => Instruction::ADC(A, L),
+            0x8E => Instruction::ADC(A, IndirectHL),
+            0x8F => Instruction::ADC(A, A),
+
+            0x90 => Instruction::SUB(A, B),
+            0x91 => Instruction::SUB(A, C),
+            0x92 => Instruction::SUB(A, D),
+            0x93 => Instruction::SUB(A, E),
+            0x94 => Instruction::SUB(A, H),
+            0x95 => Instruction::SUB(A, L),
+            0x96 => Instruction::SUB(A, IndirectHL),
+            0x97 => Instruction::SUB(A, A),
+            0x98 => Instruction::SBC(A, B),
+            0x99 => Instruction::SBC(A, C),
+            0x9A => Instruction::SBC(A, D),
+            0x9B => Instruction::SBC(A, E),
+            0x9C => Instruction::SBC(A, H),
+            0x9D => Instruction::SBC(A, L),
+            0x9E => Instruction::SBC(A, IndirectHL),
+            0x9F => Instruction::SBC(A, A),
+
+            0xA0 => Instruction::AND(A, B),
+            0xA1 => Instruction::AND(A, C),
+            0xA2 => Instruction::AND(A, D),
+            0xA3 => Instruction::AND(A, E),
+            0xA4 => Instruction::AND(A, H),
+            0xA5 => Instruction::AND(A, L),
+            0xA6 => Instruction::AND(A, IndirectHL),
+            0xA7 => Instruction::AND(A, A),
+            0xA8 => Instruction::XOR(A, B),
+            0xA9 => Instruction::XOR(A, C),
+            0xAA => Instruction::XOR(A, D),
+            0xAB => Instruction::XOR(A, E),
+            0xAC => Instruction::XOR(A, H),
+            0xAD => Instruction::XOR(A, L),
+            0xAE => Instruction::XOR(A, IndirectHL),
+            0xAF => Instruction::XOR(A, A),
+
+            0xB0 => Instruction::OR(A, B),
+            0xB1 => Instruction::OR(A, C),
+            0xB2 => Instruction::OR(A, D),
+            0xB3 => Instruction::OR(A, E),
+            0xB4 => Instruction::OR(A, H),
+            0xB5 => Instruction::OR(A, L),
+            0xB6 => Instruction::OR(A, IndirectHL),
+            0xB7 => Instruction::OR(A, A),
+            0xB8 => Instruction::CP(A, B),
+            0xB9 => Instruction::CP(A, C),
+            0xBA => Instruction::CP(A, D),
+            0xBB => Instruction::CP(A, E),
+            0xBC => Instruction::CP(A, H),
+            0xBD => Instruction::CP(A, L),
+            0xBE => Instruction::CP(A, IndirectHL),
+            0xBF => Instruction::CP(A, A),
+
+            0xC0 => Instruction::RETCC(JRTarget::NZ),
+            0xC1 => Instruction::POP(BC),
+            0xC2 => Instruction::JPCC(JRTarget::NZ),
+            0xC3 => Instruction::JP(U16),
+            0xC4 => Instruction::CALLCC(JRTarget::NZ),
+            0xC5 => Instruction::PUSH(BC),
+            0xC6 => Instruction::ADD(A, U8),
+            0xC7 => Instruction::RST(0x00),
+            0xC8 => Instruction::RETCC(JRTarget::Z),
+            0xC9 => Instruction::RET,
+            0xCA => Instruction::JPCC(JRTarget::Z),
+            0xCB => Instruction::PREFIX,
+            0xCC => Instruction::CALLCC(JRTarget::Z),
+            0xCD => Instruction::CALL,
+            0xCE => Instruction::ADC(A, U8),
+            0xCF => Instruction::RST(0x08),
+
+            0xD0 => Instruction::RETCC(JRTarget::NC),
+            0xD1 => Instruction::POP(DE),
+            0xD2 => Instruction::JPCC(JRTarget::NC),
+            0xD4 => Instruction::CALLCC(JRTarget::NC),
+            0xD5 => Instruction::PUSH(DE),
+            0xD6 => Instruction::SUB(A, U8),
+            0xD7 => Instruction::RST(0x10),
+            0xD8 => Instruction::RETCC(JRTarget::C),
+            0xD9 => Instruction::RETI,
+            0xDA => Instruction::JPCC(JRTarget::C),
+            0xDC => Instruction::CALLCC(JRTarget::C),
+            0xDE => Instruction::SBC(A, U8),
+            0xDF => Instruction::RST(0x18),
+
+            0xE0 => Instruction::LD(HighU8, A),
+            0xE1 => Instruction::POP(HL),
+            0xE2 => Instruction::LD(HighC, A),
+            0xE5 => Instruction::PUSH(HL),
+            0xE6 => Instruction::AND(A, U8),
+            0xE7 => Instruction::RST(0x20),
+            0xE8 => Instruction::ADD(SP, I8),
+            0xE9 => Instruction::JP(HL),
+            0xEA => Instruction::LD(IndirectU16, A),
+            0xEE => Instruction::XOR(A, U8),
+            0xEF => Instruction::RST(0x28),
+
+            0xF0 => Instruction::LD(A, HighU8),
+            0xF1 => Instruction::POP(AF),
+            0xF2 => Instruction::LD(A, HighC),
             0xF3 => Instruction::DI,
-            0xE0 => Instruction::LD(U8, A),
+            0xF5 => Instruction::PUSH(AF),
+            0xF6 => Instruction::OR(A, U8),
+            0xF7 => Instruction::RST(0x30),
+            0xF8 => Instruction::LD(HL, SPI8),
+            0xF9 => Instruction::LD(SP, HL),
+            0xFA => Instruction::LD(A, IndirectU16),
+            0xFB => Instruction::EI,
+            0xFE => Instruction::CP(A, U8),
+            0xFF => Instruction::RST(0x38),
+
+            0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD => Instruction::ILLEGAL(byte),
+        }
+    }
 
-            _ => Instruction::UNKNOWN(byte)
+    // the second byte after 0xCB: bits 0-2 pick the operand, bits 3-5 the operation or bit index
+    pub fn from_prefixed_byte(byte: u8) -> Instruction {
+        let target = match byte & 0x07 {
+            0 => B,
+            1 => C,
+            2 => D,
+            3 => E,
+            4 => H,
+            5 => L,
+            6 => IndirectHL,
+            _ => A,
+        };
+        let bit = (byte >> 3) & 0x07;
+
+        match byte {
+            0x00..=0x07 => Instruction::RLC(target),
+            0x08..=0x0F => Instruction::RRC(target),
+            0x10..=0x17 => Instruction::RL(target),
+            0x18..=0x1F => Instruction::RR(target),
+            0x20..=0x27 => Instruction::SLA(target),
+            0x28..=0x2F => Instruction::SRA(target),
+            0x30..=0x37 => Instruction::SWAP(target),
+            0x38..=0x3F => Instruction::SRL(target),
+            0x40..=0x7F => Instruction::BIT(bit, target),
+            0x80..=0xBF => Instruction::RES(bit, target),
+            0xC0..=0xFF => Instruction::SET(bit, target),
         }
     }
 }
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            A => "A",
+            B => "B",
+            C => "C",
+            D => "D",
+            E => "E",
+            H => "H",
+            L => "L",
+            AF => "AF",
+            BC => "BC",
+            DE => "DE",
+            HL => "HL",
+            SP => "SP",
+            U8 => "u8",
+            U16 => "u16",
+            I8 => "i8",
+            IndirectBC => "(BC)",
+            IndirectDE => "(DE)",
+            IndirectHL => "(HL)",
+            IndirectU16 => "(u16)",
+            HighC => "(FF00+C)",
+            HighU8 => "(FF00+u8)",
+            SPI8 => "SP+i8",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for JRTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            JRTarget::Z => "Z",
+            JRTarget::NZ => "NZ",
+            JRTarget::C => "C",
+            JRTarget::NC => "NC",
+            JRTarget::I8 => "i8",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::PREFIX => write!(f, "PREFIX CB"),
+            Instruction::ILLEGAL(opcode) => write!(f, "ILLEGAL 0x{:02X}", opcode),
+            Instruction::NOP => write!(f, "NOP"),
+            Instruction::STOP => write!(f, "STOP"),
+            Instruction::HALT => write!(f, "HALT"),
+            Instruction::ADD(a, b) => write!(f, "ADD {}, {}", a, b),
+            Instruction::ADC(a, b) => write!(f, "ADC {}, {}", a, b),
+            Instruction::SUB(a, b) => write!(f, "SUB {}, {}", a, b),
+            Instruction::SBC(a, b) => write!(f, "SBC {}, {}", a, b),
+            Instruction::AND(a, b) => write!(f, "AND {}, {}", a, b),
+            Instruction::XOR(a, b) => write!(f, "XOR {}, {}", a, b),
+            Instruction::OR(a, b) => write!(f, "OR {}, {}", a, b),
+            Instruction::CP(a, b) => write!(f, "CP {}, {}", a, b),
+            Instruction::INC(target) => write!(f, "INC {}", target),
+            Instruction::DEC(target) => write!(f, "DEC {}", target),
+            Instruction::LD(a, b) => write!(f, "LD {}, {}", a, b),
+            Instruction::LDI(IndirectHL, b) => write!(f, "LD (HL+), {}", b),
+            Instruction::LDI(a, _) => write!(f, "LD {}, (HL+)", a),
+            Instruction::LDD(IndirectHL, b) => write!(f, "LD (HL-), {}", b),
+            Instruction::LDD(a, _) => write!(f, "LD {}, (HL-)", a),
+            Instruction::PUSH(target) => write!(f, "PUSH {}", target),
+            Instruction::POP(target) => write!(f, "POP {}", target),
+            Instruction::JP(target) => write!(f, "JP {}", target),
+            Instruction::JPCC(condition) => write!(f, "JP {}, u16", condition),
+            Instruction::JR(JRTarget::I8) => write!(f, "JR i8"),
+            Instruction::JR(condition) => write!(f, "JR {}, i8", condition),
+            Instruction::CALL => write!(f, "CALL u16"),
+            Instruction::CALLCC(condition) => write!(f, "CALL {}, u16", condition),
+            Instruction::RET => write!(f, "RET"),
+            Instruction::RETCC(condition) => write!(f, "RET {}", condition),
+            Instruction::RETI => write!(f, "RETI"),
+            Instruction::RST(vector) => write!(f, "RST 0x{:02X}", vector),
+            Instruction::RLCA => write!(f, "RLCA"),
+            Instruction::RRCA => write!(f, "RRCA"),
+            Instruction::RLA => write!(f, "RLA"),
+            Instruction::RRA => write!(f, "RRA"),
+            Instruction::DAA => write!(f, "DAA"),
+            Instruction::CPL => write!(f, "CPL"),
+            Instruction::SCF => write!(f, "SCF"),
+            Instruction::CCF => write!(f, "CCF"),
+            Instruction::DI => write!(f, "DI"),
+            Instruction::EI => write!(f, "EI"),
+            Instruction::RLC(target) => write!(f, "RLC {}", target),
+            Instruction::RRC(target) => write!(f, "RRC {}", target),
+            Instruction::RL(target) => write!(f, "RL {}", target),
+            Instruction::RR(target) => write!(f, "RR {}", target),
+            Instruction::SLA(target) => write!(f, "SLA {}", target),
+            Instruction::SRA(target) => write!(f, "SRA {}", target),
+            Instruction::SWAP(target) => write!(f, "SWAP {}", target),
+            Instruction::SRL(target) => write!(f, "SRL {}", target),
+            Instruction::BIT(bit, target) => write!(f, "BIT {}, {}", bit, target),
+            Instruction::RES(bit, target) => write!(f, "RES {}, {}", bit, target),
+            Instruction::SET(bit, target) => write!(f, "SET {}, {}", bit, target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cpu::instructions::Instruction;
+    use crate::hardware::cpu::instructions::Target::{A, B, H, IndirectHL, L};
+
+    #[test]
+    fn decodes_prefix() {
+        assert_eq!(Instruction::from_byte(0xCB), Instruction::PREFIX);
+    }
+
+    #[test]
+    fn decodes_prefixed_opcodes() {
+        assert_eq!(Instruction::from_prefixed_byte(0x00), Instruction::RLC(B));
+        assert_eq!(Instruction::from_prefixed_byte(0x1E), Instruction::RR(IndirectHL));
+        assert_eq!(Instruction::from_prefixed_byte(0x37), Instruction::SWAP(A));
+        assert_eq!(Instruction::from_prefixed_byte(0x7C), Instruction::BIT(7, H));
+        assert_eq!(Instruction::from_prefixed_byte(0x85), Instruction::RES(0, L));
+        assert_eq!(Instruction::from_prefixed_byte(0xFE), Instruction::SET(7, IndirectHL));
+    }
+
+    #[test]
+    fn decodes_illegal_opcodes() {
+        let illegal = (0..=0xFFu8)
+            .filter(|&byte| Instruction::from_byte(byte) == Instruction::ILLEGAL(byte))
+            .count();
+        assert_eq!(illegal, 11);
+    }
+}
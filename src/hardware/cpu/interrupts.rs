@@ -0,0 +1,199 @@
+use crate::hardware::cpu::bus::Bus;
+use crate::hardware::cpu::CPU;
+
+pub const INTERRUPT_FLAG: u16 = 0xFF0F;
+pub const INTERRUPT_ENABLE: u16 = 0xFFFF;
+
+// listed in priority order, bit 0 wins
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interrupt {
+    VBlank = 0b0000_0001,
+    LCDStat = 0b0000_0010,
+    Timer = 0b0000_0100,
+    Serial = 0b0000_1000,
+    Joypad = 0b0001_0000,
+}
+
+impl Interrupt {
+    pub const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::LCDStat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    pub fn vector(self) -> u16 {
+        match self {
+            Interrupt::VBlank => 0x40,
+            Interrupt::LCDStat => 0x48,
+            Interrupt::Timer => 0x50,
+            Interrupt::Serial => 0x58,
+            Interrupt::Joypad => 0x60,
+        }
+    }
+
+    // the highest priority interrupt among the set bits of mask
+    pub fn highest(mask: u8) -> Option<Interrupt> {
+        Interrupt::ALL.into_iter().find(|&interrupt| mask & interrupt as u8 != 0)
+    }
+}
+
+// the IE (0xFFFF) and IF (0xFF0F) registers
+pub struct Interrupts {
+    pub enable: u8,
+    pub flag: u8,
+}
+
+impl Interrupts {
+    pub fn new() -> Self {
+        Interrupts {
+            enable: 0x00,
+            flag: 0xE0,
+        }
+    }
+
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.flag |= interrupt as u8;
+    }
+
+    pub fn acknowledge(&mut self, interrupt: Interrupt) {
+        self.flag &= !(interrupt as u8);
+    }
+
+    // requested and enabled, regardless of IME
+    pub fn pending(&self) -> u8 {
+        self.enable & self.flag & 0x1F
+    }
+
+    // only the lower 5 bits of IF exist, the rest read back as 1
+    pub fn read_flag(&self) -> u8 {
+        self.flag | 0xE0
+    }
+
+    pub fn write_flag(&mut self, value: u8) {
+        self.flag = value | 0xE0;
+    }
+}
+
+impl Default for Interrupts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CPU {
+    // 5 M-cycles: two wait states, PC pushed one byte at a time, then the jump
+    pub fn dispatch_interrupt<B: Bus>(&mut self, bus: &mut B) {
+        self.ime = false;
+        self.tick(bus);
+        self.tick(bus);
+
+        let pc = self.registers.pc;
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
+        self.write(bus, self.registers.sp, (pc >> 8) as u8);
+
+        // the vector is picked after the upper byte is pushed, so a push that lands on IE can
+        // change it or cancel the dispatch altogether, in which case execution continues at 0x0000
+        let interrupt = Interrupt::highest(bus.pending_interrupts());
+
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
+        self.write(bus, self.registers.sp, pc as u8);
+
+        self.registers.pc = match interrupt {
+            Some(interrupt) => {
+                bus.acknowledge_interrupt(interrupt);
+                interrupt.vector()
+            }
+            None => 0x0000,
+        };
+        self.tick(bus);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cartridge::Cartridge;
+    use crate::hardware::cpu::bus::SystemBus;
+    use crate::hardware::cpu::CPU;
+    use crate::hardware::cpu::interrupts::Interrupt;
+
+    // with the stack at the top of HRAM and nothing requested
+    fn cpu_with_program(program: &[u8]) -> (CPU, SystemBus) {
+        let mut cpu = CPU::new();
+        cpu.registers.sp = 0xFFFE;
+        (cpu, SystemBus::new(Cartridge::with_program(0x100, program)))
+    }
+
+    #[test]
+    fn dispatch_jumps_to_vector() {
+        let (mut cpu, mut bus) = cpu_with_program(&[]);
+        cpu.ime = true;
+        cpu.registers.sp = 0xD000;
+        bus.interrupts.enable = 0x1F;
+        bus.interrupts.request(Interrupt::Timer);
+        bus.interrupts.request(Interrupt::Serial);
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(cpu.registers.pc, 0x50);
+        assert_eq!(cpu.registers.sp, 0xCFFE);
+        assert!(!cpu.ime);
+        assert_eq!(bus.interrupts.read_flag(), 0xE0 | Interrupt::Serial as u8);
+    }
+
+    #[test]
+    fn disabled_interrupts_are_not_dispatched() {
+        let (mut cpu, mut bus) = cpu_with_program(&[]);
+        cpu.ime = true;
+        bus.interrupts.enable = Interrupt::Joypad as u8;
+        bus.interrupts.request(Interrupt::VBlank);
+
+        assert_eq!(cpu.step(&mut bus), 1);
+        assert_eq!(cpu.registers.pc, 0x101);
+    }
+
+    #[test]
+    fn ei_takes_effect_after_the_next_instruction() {
+        // EI, NOP, NOP
+        let (mut cpu, mut bus) = cpu_with_program(&[0xFB, 0x00, 0x00]);
+        bus.interrupts.enable = Interrupt::VBlank as u8;
+        bus.interrupts.request(Interrupt::VBlank);
+
+        cpu.step(&mut bus);
+        assert!(!cpu.ime);
+        cpu.step(&mut bus);
+        assert!(cpu.ime);
+        assert_eq!(cpu.registers.pc, 0x102);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.registers.pc, 0x40);
+    }
+
+    #[test]
+    fn di_cancels_pending_ei() {
+        // EI, DI, NOP
+        let (mut cpu, mut bus) = cpu_with_program(&[0xFB, 0xF3, 0x00]);
+        bus.interrupts.enable = Interrupt::VBlank as u8;
+        bus.interrupts.request(Interrupt::VBlank);
+
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert!(!cpu.ime);
+        assert_eq!(cpu.registers.pc, 0x103);
+    }
+
+    #[test]
+    fn push_onto_ie_cancels_dispatch() {
+        let (mut cpu, mut bus) = cpu_with_program(&[]);
+        cpu.ime = true;
+        // the upper byte of PC (0x01) lands on IE and leaves only the VBlank interrupt enabled
+        cpu.registers.sp = 0x0000;
+        bus.interrupts.enable = Interrupt::Timer as u8;
+        bus.interrupts.request(Interrupt::Timer);
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(cpu.registers.pc, 0x0000);
+        assert_eq!(bus.interrupts.enable, 0x01);
+        assert_eq!(bus.interrupts.read_flag(), 0xE0 | Interrupt::Timer as u8);
+    }
+}
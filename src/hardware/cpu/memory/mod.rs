@@ -1,18 +1,27 @@
-pub const MAX_RAM: usize = u16::MAX as usize;
+pub const WRAM_SIZE: usize = 0x2000;
+pub const IO_SIZE: usize = 0x80;
+pub const HRAM_SIZE: usize = 0x7F;
 
+// the memory that lives inside the console, the bus translates addresses into offsets
 pub struct Memory {
-    cells: Vec<u8>,
+    pub wram: Vec<u8>,
+    // registers that no peripheral has claimed yet
+    pub io: Vec<u8>,
+    pub hram: Vec<u8>,
 }
 
 impl Memory {
     pub fn new() -> Memory {
         Memory {
-            cells: vec![0; MAX_RAM]
+            wram: vec![0; WRAM_SIZE],
+            io: vec![0xFF; IO_SIZE],
+            hram: vec![0; HRAM_SIZE],
         }
     }
+}
 
-    pub fn length(&self) -> usize {
-        self.cells.len()
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
     }
 }
-
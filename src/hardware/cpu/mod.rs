@@ -1,11 +1,9 @@
-use memory::Memory;
+use bus::Bus;
 use registers::flags::Flag;
 use registers::Registers;
 
-use crate::hardware::cartridge::Cartridge;
 use crate::hardware::cpu::instructions::{Instruction, JRTarget, Target};
-use crate::hardware::cpu::instructions::Target::{A, B, BC, C, D, DE, E, H, HL, I8, L, SP, U16, U8};
-use crate::hardware::utils;
+use crate::hardware::cpu::instructions::Target::{A, AF, B, BC, C, D, DE, E, H, HighC, HighU8, HL, I8, IndirectBC, IndirectDE, IndirectHL, IndirectU16, L, SP, SPI8, U16, U8};
 use crate::hardware::utils::concatenate_bytes;
 
 pub mod instructions;
@@ -13,13 +11,23 @@ pub mod bus;
 pub mod alu;
 pub mod registers;
 pub mod memory;
+pub mod interrupts;
 
+// the SM83 core, it owns no memory and runs on whatever bus it is handed each step
 pub struct CPU {
-    pub memory: Memory,
     pub registers: Registers,
-    pub cartridge: Cartridge,
     pub ime: bool,
+    // set by EI, IME turns on once the following instruction has run
+    ime_scheduled: bool,
     pub is_running: bool,
+    // HALT: no instructions run until an interrupt is pending, time keeps passing
+    pub is_halted: bool,
+    // STOP: the system clock is stopped until a joypad line goes low
+    pub is_stopped: bool,
+    // the joypad lines as last seen while stopped, a pressed button that was already held doesn't count
+    stop_lines: u8,
+    // HALT with IME off and an interrupt already pending fails to increment PC on the next fetch
+    halt_bug: bool,
     pub cycles: Cycles,
 }
 
@@ -28,14 +36,36 @@ pub struct Cycles {
     clock: usize,
 }
 
+impl Cycles {
+    // M-cycles executed since power on
+    pub fn machine(&self) -> usize {
+        self.machine
+    }
+
+    // T-cycles (4 per M-cycle) executed since power on
+    pub fn clock(&self) -> usize {
+        self.clock
+    }
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        CPU::new()
+    }
+}
+
 impl CPU {
-    pub fn new(cartridge: Cartridge) -> Self {
+    // runs from 0x0100 with zeroed registers
+    pub fn new() -> Self {
         CPU {
-            memory: Memory::new(),
             registers: Registers::new(),
-            cartridge,
             ime: false,
+            ime_scheduled: false,
             is_running: true,
+            is_halted: false,
+            is_stopped: false,
+            stop_lines: 0x0F,
+            halt_bug: false,
             cycles: Cycles {
                 machine: 0,
                 clock: 0,
@@ -43,15 +73,14 @@ impl CPU {
         }
     }
 
-
     pub fn fetch_and_increment_pc(&mut self) -> u16 {
         let pc = self.registers.pc;
-        self.registers.pc += 1;
+        self.registers.pc = pc.wrapping_add(1);
         pc
     }
 
     pub fn increment_pc(&mut self) {
-        self.registers.pc += 1
+        self.registers.pc = self.registers.pc.wrapping_add(1)
     }
 
     pub fn stop(&mut self) {
@@ -62,222 +91,627 @@ impl CPU {
         self.is_running = true;
     }
 
-    pub fn step(&mut self) {
-        let pc = self.fetch_and_increment_pc();
-        let instruction = Instruction::from_byte(self.bus_read(pc));
-        self.execute(instruction);
+    // executes one instruction and returns how many M-cycles it took
+    pub fn step<B: Bus>(&mut self, bus: &mut B) -> usize {
+        let start = self.cycles.machine;
+        if self.is_stopped {
+            // IF.joypad may have been set long before, only a line going low now ends STOP
+            let lines = bus.joypad_lines();
+            let fallen = self.stop_lines & !lines;
+            self.stop_lines = lines;
+            if fallen == 0 {
+                return 0;
+            }
+            self.is_stopped = false;
+        }
+
+        if self.is_halted {
+            // any pending interrupt ends HALT, whether or not it will be serviced
+            if bus.pending_interrupts() == 0 {
+                self.tick(bus);
+                return self.cycles.machine - start;
+            }
+            self.is_halted = false;
+        }
+
+        if self.ime && bus.pending_interrupts() != 0 {
+            self.dispatch_interrupt(bus);
+            return self.cycles.machine - start;
+        }
+
+        let enable_ime = self.ime_scheduled;
+        let opcode = if self.halt_bug {
+            self.halt_bug = false;
+            self.read(bus, self.registers.pc)
+        } else {
+            self.fetch_byte(bus)
+        };
+        let instruction = match Instruction::from_byte(opcode) {
+            Instruction::PREFIX => {
+                let opcode = self.fetch_byte(bus);
+                Instruction::from_prefixed_byte(opcode)
+            }
+            instruction => instruction,
+        };
+        self.execute(bus, instruction);
+        // a DI right after EI clears the schedule before it takes effect
+        if enable_ime && self.ime_scheduled {
+            self.ime = true;
+            self.ime_scheduled = false;
+        }
+        self.cycles.machine - start
     }
 
     pub fn get_ime(&self) -> bool {
         self.ime
     }
 
-    pub fn execute(&mut self, instruction: Instruction) {
-        print!("0x{:x} ", self.registers.pc - 1);
+    // every bus access takes one M-cycle, internal operations call tick on their own
+    fn tick<B: Bus>(&mut self, bus: &mut B) {
+        self.cycles.machine += 1;
+        self.cycles.clock += 4;
+        bus.tick();
+    }
+
+    fn read<B: Bus>(&mut self, bus: &mut B, address: u16) -> u8 {
+        let value = bus.read(address);
+        self.tick(bus);
+        value
+    }
+
+    fn write<B: Bus>(&mut self, bus: &mut B, address: u16, value: u8) {
+        bus.write(address, value);
+        self.tick(bus);
+    }
+
+    fn fetch_byte<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let pc = self.fetch_and_increment_pc();
+        self.read(bus, pc)
+    }
+
+    fn fetch_word<B: Bus>(&mut self, bus: &mut B) -> u16 {
+        let lower = self.fetch_byte(bus);
+        let higher = self.fetch_byte(bus);
+        concatenate_bytes(lower, higher)
+    }
+
+    fn push<B: Bus>(&mut self, bus: &mut B, value: u16) {
+        self.tick(bus);
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
+        self.write(bus, self.registers.sp, (value >> 8) as u8);
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
+        self.write(bus, self.registers.sp, value as u8);
+    }
+
+    fn pop<B: Bus>(&mut self, bus: &mut B) -> u16 {
+        let lower = self.read(bus, self.registers.sp);
+        self.registers.sp = self.registers.sp.wrapping_add(1);
+        let higher = self.read(bus, self.registers.sp);
+        self.registers.sp = self.registers.sp.wrapping_add(1);
+        concatenate_bytes(lower, higher)
+    }
+
+    fn read_u8<B: Bus>(&mut self, bus: &mut B, target: Target) -> u8 {
+        match target {
+            A => self.registers.a,
+            B => self.registers.b,
+            C => self.registers.c,
+            D => self.registers.d,
+            E => self.registers.e,
+            H => self.registers.h,
+            L => self.registers.l,
+            U8 => self.fetch_byte(bus),
+            IndirectBC => self.read(bus, self.registers.get_bc()),
+            IndirectDE => self.read(bus, self.registers.get_de()),
+            IndirectHL => self.read(bus, self.registers.get_hl()),
+            IndirectU16 => {
+                let address = self.fetch_word(bus);
+                self.read(bus, address)
+            }
+            HighC => self.read(bus, 0xFF00 | self.registers.c as u16),
+            HighU8 => {
+                let offset = self.fetch_byte(bus);
+                self.read(bus, 0xFF00 | offset as u16)
+            }
+            _ => unreachable!("{:?} is not an 8-bit source", target)
+        }
+    }
+
+    fn write_u8<B: Bus>(&mut self, bus: &mut B, target: Target, value: u8) {
+        match target {
+            A => self.registers.a = value,
+            B => self.registers.b = value,
+            C => self.registers.c = value,
+            D => self.registers.d = value,
+            E => self.registers.e = value,
+            H => self.registers.h = value,
+            L => self.registers.l = value,
+            IndirectBC => self.write(bus, self.registers.get_bc(), value),
+            IndirectDE => self.write(bus, self.registers.get_de(), value),
+            IndirectHL => self.write(bus, self.registers.get_hl(), value),
+            IndirectU16 => {
+                let address = self.fetch_word(bus);
+                self.write(bus, address, value)
+            }
+            HighC => self.write(bus, 0xFF00 | self.registers.c as u16, value),
+            HighU8 => {
+                let offset = self.fetch_byte(bus);
+                self.write(bus, 0xFF00 | offset as u16, value)
+            }
+            _ => unreachable!("{:?} is not an 8-bit destination", target)
+        }
+    }
+
+    fn read_u16<B: Bus>(&mut self, bus: &mut B, target: Target) -> u16 {
+        match target {
+            AF => self.registers.get_af(),
+            BC => self.registers.get_bc(),
+            DE => self.registers.get_de(),
+            HL => self.registers.get_hl(),
+            SP => self.registers.sp,
+            U16 => self.fetch_word(bus),
+            _ => unreachable!("{:?} is not a 16-bit source", target)
+        }
+    }
+
+    fn write_u16(&mut self, target: Target, value: u16) {
+        match target {
+            AF => self.registers.set_af(value),
+            BC => self.registers.set_bc(value),
+            DE => self.registers.set_de(value),
+            HL => self.registers.set_hl(value),
+            SP => self.registers.sp = value,
+            _ => unreachable!("{:?} is not a 16-bit destination", target)
+        }
+    }
+
+    fn condition(&self, condition: JRTarget) -> bool {
+        match condition {
+            JRTarget::Z => self.registers.get_flag(Flag::Zero),
+            JRTarget::NZ => !self.registers.get_flag(Flag::Zero),
+            JRTarget::C => self.registers.get_flag(Flag::Carry),
+            JRTarget::NC => !self.registers.get_flag(Flag::Carry),
+            JRTarget::I8 => true,
+        }
+    }
+
+    fn is_16_bit(target: Target) -> bool {
+        matches!(target, AF | BC | DE | HL | SP)
+    }
+
+    // only ever handed what the decoder produces, PREFIX and operand combinations no opcode has
+    // would panic, so it isn't part of the public API
+    fn execute<B: Bus>(&mut self, bus: &mut B, instruction: Instruction) {
         match instruction {
-            Instruction::NOP => println!("NOP"),
-            Instruction::STOP => println!("STOP"),
+            Instruction::NOP => {}
+            Instruction::STOP => {
+                // the byte after STOP is skipped, and the divider is reset
+                self.increment_pc();
+                bus.reset_divider();
+                self.is_stopped = true;
+                self.stop_lines = bus.joypad_lines();
+            }
+            Instruction::HALT => {
+                if !self.ime && bus.pending_interrupts() != 0 {
+                    self.halt_bug = true;
+                } else {
+                    self.is_halted = true;
+                }
+            }
             Instruction::DI => {
                 self.ime = false;
-                println!("DI");
+                self.ime_scheduled = false;
+            }
+            Instruction::EI => self.ime_scheduled = true,
+            Instruction::LD(IndirectU16, SP) => {
+                let address = self.fetch_word(bus);
+                let sp = self.registers.sp;
+                self.write(bus, address, sp as u8);
+                self.write(bus, address.wrapping_add(1), (sp >> 8) as u8);
+            }
+            Instruction::LD(HL, SPI8) => {
+                let offset = self.fetch_byte(bus) as i8;
+                let value = self.alu_add_sp(offset);
+                self.registers.set_hl(value);
+                self.tick(bus);
+            }
+            Instruction::LD(SP, HL) => {
+                self.registers.sp = self.registers.get_hl();
+                self.tick(bus);
+            }
+            Instruction::LD(a, b) if Self::is_16_bit(a) => {
+                let value = self.read_u16(bus, b);
+                self.write_u16(a, value);
             }
             Instruction::LD(a, b) => {
-                match (a, b) {
-                    (U8, A) => {
-                        unimplemented!("LD u8, A")
-                    }
-                    (A, U8) => {
-                        let mut pc = self.fetch_and_increment_pc();
-                        let value = self.bus_read(pc);
-                        self.registers.a = value;
-
-                        println!("LD A, {:X}", value);
-                    }
-                    (U16, A) => {
-                        let mut pc = self.fetch_and_increment_pc();
-                        let lower = self.bus_read(pc);
-                        pc = self.fetch_and_increment_pc();
-                        let higher = self.bus_read(pc);
-
-                        let value = concatenate_bytes(lower, higher);
-                        self.bus_write(value, self.registers.a);
-
-                        println!("LD 0x{:X}, A", value);
-                    }
-                    (HL, U16) => {
-                        let mut pc = self.fetch_and_increment_pc();
-                        let lower = self.bus_read(pc);
-                        pc = self.fetch_and_increment_pc();
-                        let higher = self.bus_read(pc);
-
-                        let value = concatenate_bytes(lower, higher);
-                        self.registers.set_hl(value);
-
-                        println!("LD HL, 0x{:X}", value);
-                    }
-                    (C, U8) => {
-                        let mut pc = self.fetch_and_increment_pc();
-                        let value = self.bus_read(pc);
-                        self.registers.c = value;
-
-                        println!("LD C, 0x{:X}", value);
-                    }
-                    (B, U8) => {
-                        let mut pc = self.fetch_and_increment_pc();
-                        let value = self.bus_read(pc);
-                        self.registers.b = value;
-
-                        println!("LD B, 0x{:X}", value);
-                    }
-                    _ => { unimplemented!("LD {:?}, {:?}", a, b) }
-                }
+                let value = self.read_u8(bus, b);
+                self.write_u8(bus, a, value);
+            }
+            Instruction::LDI(a, b) => {
+                let value = self.read_u8(bus, b);
+                self.write_u8(bus, a, value);
+                let hl = self.registers.get_hl();
+                self.registers.set_hl(hl.wrapping_add(1));
             }
             Instruction::LDD(a, b) => {
-                match (a, b) {
-                    (HL, A) => {
-                        let hl = self.registers.get_hl();
-                        self.bus_write(hl, self.registers.a);
-                        self.registers.set_hl(hl - 1);
-                        println!("LDD (HL--), A");
-                    }
-                    _ => { unimplemented!("LDD {:?}, {:?}", a, b) }
-                }
+                let value = self.read_u8(bus, b);
+                self.write_u8(bus, a, value);
+                let hl = self.registers.get_hl();
+                self.registers.set_hl(hl.wrapping_sub(1));
+            }
+            Instruction::PUSH(target) => {
+                let value = self.read_u16(bus, target);
+                self.push(bus, value);
+            }
+            Instruction::POP(target) => {
+                let value = self.pop(bus);
+                self.write_u16(target, value);
+            }
+            Instruction::ADD(SP, I8) => {
+                let offset = self.fetch_byte(bus) as i8;
+                self.registers.sp = self.alu_add_sp(offset);
+                self.tick(bus);
+                self.tick(bus);
+            }
+            Instruction::ADD(HL, b) => {
+                let value = self.read_u16(bus, b);
+                self.alu_add_hl(value);
+                self.tick(bus);
+            }
+            Instruction::ADD(_, b) => {
+                let value = self.read_u8(bus, b);
+                self.registers.a = self.alu_add(self.registers.a, value);
+            }
+            Instruction::ADC(_, b) => {
+                let value = self.read_u8(bus, b);
+                self.registers.a = self.alu_adc(self.registers.a, value);
+            }
+            Instruction::SUB(_, b) => {
+                let value = self.read_u8(bus, b);
+                self.registers.a = self.alu_sub(self.registers.a, value);
+            }
+            Instruction::SBC(_, b) => {
+                let value = self.read_u8(bus, b);
+                self.registers.a = self.alu_sbc(self.registers.a, value);
+            }
+            Instruction::AND(_, b) => {
+                let value = self.read_u8(bus, b);
+                self.registers.a = self.alu_and(self.registers.a, value);
+            }
+            Instruction::XOR(_, b) => {
+                let value = self.read_u8(bus, b);
+                self.registers.a = self.alu_xor(self.registers.a, value);
+            }
+            Instruction::OR(_, b) => {
+                let value = self.read_u8(bus, b);
+                self.registers.a = self.alu_or(self.registers.a, value);
+            }
+            Instruction::CP(_, b) => {
+                let value = self.read_u8(bus, b);
+                self.alu_cp(self.registers.a, value);
+            }
+            Instruction::INC(target) if Self::is_16_bit(target) => {
+                let value = self.read_u16(bus, target);
+                self.write_u16(target, value.wrapping_add(1));
+                self.tick(bus);
+            }
+            Instruction::INC(target) => {
+                let value = self.read_u8(bus, target);
+                let result = self.alu_inc(value);
+                self.write_u8(bus, target, result);
+            }
+            Instruction::DEC(target) if Self::is_16_bit(target) => {
+                let value = self.read_u16(bus, target);
+                self.write_u16(target, value.wrapping_sub(1));
+                self.tick(bus);
             }
-            Instruction::ADD(a, b) => println!("ADD {:?}, {:?}", a, b),
-            Instruction::INC(target) => println!("INC {:?}", target),
             Instruction::DEC(target) => {
-                match target {
-                    B => {
-                        // let result = self.registers.b.wrapping_sub(1);
-                        // self.registers.b = result;
-                        // self.registers.set_flag(Flag::Zero, result == 0);
-                        // self.registers.set_flag(Flag::Negative, true);
-                        self.registers.b = self.alu_sub(self.registers.b, 1);
-                        println!("DEC B");
-                    }
-                    C => {
-                        // let result = self.registers.b.wrapping_sub(1);
-                        // self.registers.b = result;
-                        // self.registers.set_flag(Flag::Zero, result == 0);
-                        // self.registers.set_flag(Flag::Negative, true);
-                        self.registers.c = self.alu_sub(self.registers.c, 1);
-                        println!("DEC C");
-                    }
-                    _ => { unimplemented!("DEC {:?}", target) }
-                }
+                let value = self.read_u8(bus, target);
+                let result = self.alu_dec(value);
+                self.write_u8(bus, target, result);
             }
             Instruction::RLCA => {
-                println!("RLCA");
-                unimplemented!("RLCA")
+                self.registers.a = self.alu_rlc(self.registers.a);
+                self.registers.set_flag(Flag::Zero, false);
             }
             Instruction::RRCA => {
-                println!("RRCA");
-                unimplemented!("RRCA");
-            }
-            Instruction::CP(a, b) => {
-                match (a, b) {
-                    (A, U8) => {
-                        let mut pc = self.fetch_and_increment_pc();
-                        let value1 = self.registers.a;
-                        let value2 = self.bus_read(pc);
-
-                        let total = value1.wrapping_sub(value2);
-                        // self.registers.set_flag(Flag::Zero, total == 0);
-                        // self.registers.set_flag(Flag::Negative, true);
-                        // self.registers.set_flag(Flag::HalfCarry, (value1 & 0x0F) > (value2 & 0x0F));
-                        // self.registers.set_flag(Flag::Carry, value1 > value2);
-                        self.alu_sub(value1, value2);
-                        println!("CP A, {:x}", value2)
-                    }
-                    _ => { unimplemented!("CP {:?}, {:?}", a, b) }
-                }
+                self.registers.a = self.alu_rrc(self.registers.a);
+                self.registers.set_flag(Flag::Zero, false);
+            }
+            Instruction::RLA => {
+                self.registers.a = self.alu_rl(self.registers.a);
+                self.registers.set_flag(Flag::Zero, false);
             }
+            Instruction::RRA => {
+                self.registers.a = self.alu_rr(self.registers.a);
+                self.registers.set_flag(Flag::Zero, false);
+            }
+            Instruction::DAA => self.alu_daa(),
+            Instruction::CPL => self.alu_cpl(),
+            Instruction::SCF => self.alu_scf(),
+            Instruction::CCF => self.alu_ccf(),
+            Instruction::JP(HL) => self.registers.pc = self.registers.get_hl(),
             Instruction::JP(target) => {
-                match target {
-                    U16 => {
-                        let mut pc = self.fetch_and_increment_pc();
-                        let lower = self.bus_read(pc);
-                        pc = self.fetch_and_increment_pc();
-                        let higher = self.bus_read(pc);
-
-                        let value = concatenate_bytes(lower, higher);
-                        self.registers.pc = value;
-                        println!("JP 0x{:x}", value)
-                    }
-                    _ => { unimplemented!("JP {:?}", target) }
+                self.registers.pc = self.read_u16(bus, target);
+                self.tick(bus);
+            }
+            Instruction::JPCC(condition) => {
+                let address = self.fetch_word(bus);
+                if self.condition(condition) {
+                    self.registers.pc = address;
+                    self.tick(bus);
                 }
             }
-            Instruction::XOR(a, b) => {
-                match (a, b) {
-                    (A, A) => {
-                        let reg_a = self.registers.a;
-                        let result = reg_a ^ reg_a;
-                        self.registers.a = result;
-                        self.registers.set_flag(Flag::Zero, result == 0);
-                        self.registers.set_flag(Flag::Negative, false);
-                        self.registers.set_flag(Flag::HalfCarry, false);
-                        self.registers.set_flag(Flag::Carry, false);
-                        println!("XOR A, A");
-                    }
-                    _ => { unimplemented!("XOR {:?}, {:?}", a, b) }
+            Instruction::JR(condition) => {
+                let offset = self.fetch_byte(bus) as i8;
+                if self.condition(condition) {
+                    self.registers.pc = self.registers.pc.wrapping_add(offset as u16);
+                    self.tick(bus);
                 }
             }
-            Instruction::JR(target) => {
-                match target {
-                    JRTarget::Z => {
-                        let pc = self.fetch_and_increment_pc();
-                        let n = self.bus_read(pc) as i8;
-                        let addr = self.registers.pc.wrapping_add(n as u16);
-                        if self.registers.get_flag(Flag::Zero) {
-                            self.registers.pc = addr;
-                        }
-
-                        println!("JR Z, 0x{:X}", addr)
-                    }
-                    JRTarget::I8 => {
-                        let pc = self.fetch_and_increment_pc();
-                        let n = self.bus_read(pc) as i8;
-                        let addr = self.registers.pc.wrapping_add(n as u16);
-                        self.registers.pc = addr;
-                        println!("JR {:x}", addr)
-                    }
-                    JRTarget::NZ => {
-                        let pc = self.fetch_and_increment_pc();
-
-                        let n = self.bus_read(pc) as i8;
-                        let addr = self.registers.pc.wrapping_add(n as u16);
-                        if !self.registers.get_flag(Flag::Zero) {
-                            self.registers.pc = addr;
-                        }
-
-                        println!("JR NZ, 0x{:X}", addr)
-                    }
-                    JRTarget::C => {
-                        let pc = self.fetch_and_increment_pc();
-                        let n = self.bus_read(pc) as i8;
-                        let addr = self.registers.pc.wrapping_add(n as u16);
-                        if self.registers.get_flag(Flag::Carry) {
-                            self.registers.pc = addr;
-                        }
-
-                        println!("JR C, 0x{:X}", addr)
-                    }
-
-                    JRTarget::NC => {
-                        let pc = self.fetch_and_increment_pc();
-                        let n = self.bus_read(pc);
-                        let addr = ((self.registers.pc as i16) + (n as i16)) as u16;
-                        if !self.registers.get_flag(Flag::Carry) {
-                            self.registers.pc = addr;
-                        }
-
-                        println!("JR NC, 0x{:X}", addr)
-                    }
-                    _ => { unimplemented!("JR {:?}", target) }
+            Instruction::CALL => {
+                let address = self.fetch_word(bus);
+                self.push(bus, self.registers.pc);
+                self.registers.pc = address;
+            }
+            Instruction::CALLCC(condition) => {
+                let address = self.fetch_word(bus);
+                if self.condition(condition) {
+                    self.push(bus, self.registers.pc);
+                    self.registers.pc = address;
+                }
+            }
+            Instruction::RET => {
+                self.registers.pc = self.pop(bus);
+                self.tick(bus);
+            }
+            Instruction::RETCC(condition) => {
+                self.tick(bus);
+                if self.condition(condition) {
+                    self.registers.pc = self.pop(bus);
+                    self.tick(bus);
+                }
+            }
+            Instruction::RETI => {
+                self.registers.pc = self.pop(bus);
+                self.tick(bus);
+                self.ime = true;
+            }
+            Instruction::RST(vector) => {
+                self.push(bus, self.registers.pc);
+                self.registers.pc = vector as u16;
+            }
+            // the real hardware locks up until it is powered off
+            Instruction::ILLEGAL(_) => self.stop(),
+            Instruction::RLC(target) => {
+                let value = self.read_u8(bus, target);
+                let result = self.alu_rlc(value);
+                self.write_u8(bus, target, result);
+            }
+            Instruction::RRC(target) => {
+                let value = self.read_u8(bus, target);
+                let result = self.alu_rrc(value);
+                self.write_u8(bus, target, result);
+            }
+            Instruction::RL(target) => {
+                let value = self.read_u8(bus, target);
+                let result = self.alu_rl(value);
+                self.write_u8(bus, target, result);
+            }
+            Instruction::RR(target) => {
+                let value = self.read_u8(bus, target);
+                let result = self.alu_rr(value);
+                self.write_u8(bus, target, result);
+            }
+            Instruction::SLA(target) => {
+                let value = self.read_u8(bus, target);
+                let result = self.alu_sla(value);
+                self.write_u8(bus, target, result);
+            }
+            Instruction::SRA(target) => {
+                let value = self.read_u8(bus, target);
+                let result = self.alu_sra(value);
+                self.write_u8(bus, target, result);
+            }
+            Instruction::SWAP(target) => {
+                let value = self.read_u8(bus, target);
+                let result = self.alu_swap(value);
+                self.write_u8(bus, target, result);
+            }
+            Instruction::SRL(target) => {
+                let value = self.read_u8(bus, target);
+                let result = self.alu_srl(value);
+                self.write_u8(bus, target, result);
+            }
+            Instruction::BIT(bit, target) => {
+                let value = self.read_u8(bus, target);
+                self.alu_bit(bit, value);
+            }
+            Instruction::RES(bit, target) => {
+                let value = self.read_u8(bus, target);
+                self.write_u8(bus, target, value & !(1 << bit));
+            }
+            Instruction::SET(bit, target) => {
+                let value = self.read_u8(bus, target);
+                self.write_u8(bus, target, value | (1 << bit));
+            }
+            // fetched as part of step, never executed on its own
+            Instruction::PREFIX => unreachable!("PREFIX CB"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cartridge::Cartridge;
+    use crate::hardware::cpu::bus::SystemBus;
+    use crate::hardware::cpu::CPU;
+    use crate::hardware::cpu::interrupts::Interrupt;
+    use crate::hardware::joypad::Button;
+    use crate::hardware::timer::DIV;
+
+    // M-cycles per base opcode with the branch not taken, 0 for PREFIX and the illegal opcodes
+    const CYCLES: [usize; 256] = [
+        1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1,
+        1, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1,
+        2, 3, 2, 2, 1, 1, 2, 1, 2, 2, 2, 2, 1, 1, 2, 1,
+        2, 3, 2, 2, 3, 3, 3, 1, 2, 2, 2, 2, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        2, 3, 3, 4, 3, 4, 2, 4, 2, 4, 3, 0, 3, 6, 2, 4,
+        2, 3, 3, 0, 3, 4, 2, 4, 2, 4, 3, 0, 3, 0, 2, 4,
+        3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4,
+        3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4,
+    ];
+
+    const Z: u8 = 0x80;
+    const C: u8 = 0x10;
+
+    fn run(opcodes: &[u8], f: u8) -> usize {
+        let mut bus = SystemBus::new(Cartridge::with_program(0x0000, opcodes));
+        let mut cpu = CPU::new();
+        cpu.registers.pc = 0;
+        cpu.registers.f = f;
+        let cycles = cpu.step(&mut bus);
+        assert_eq!(cycles, cpu.cycles.machine());
+        assert_eq!(cpu.cycles.clock(), cycles * 4);
+        cycles
+    }
+
+    // the extra cost of a taken branch, given the flags it was run with
+    fn taken_cycles(opcode: u8, f: u8) -> usize {
+        let taken = match opcode {
+            0x20 | 0xC0 | 0xC2 | 0xC4 => f & Z == 0,
+            0x28 | 0xC8 | 0xCA | 0xCC => f & Z != 0,
+            0x30 | 0xD0 | 0xD2 | 0xD4 => f & C == 0,
+            0x38 | 0xD8 | 0xDA | 0xDC => f & C != 0,
+            _ => false,
+        };
+        match opcode {
+            _ if !taken => 0,
+            0x20 | 0x28 | 0x30 | 0x38 | 0xC2 | 0xCA | 0xD2 | 0xDA => 1,
+            // CALL cc pushes PC and RET cc pops it
+            _ => 3,
+        }
+    }
+
+    // with zeroed registers and nothing requested
+    fn cpu_with_program(program: &[u8]) -> (CPU, SystemBus) {
+        (CPU::new(), SystemBus::new(Cartridge::with_program(0x100, program)))
+    }
+
+    #[test]
+    fn halt_keeps_time_running() {
+        // HALT, INC A
+        let (mut cpu, mut bus) = cpu_with_program(&[0x76, 0x3C]);
+        cpu.step(&mut bus);
+        assert!(cpu.is_halted);
+        for _ in 0..10 {
+            assert_eq!(cpu.step(&mut bus), 1);
+        }
+        assert_eq!(cpu.registers.pc, 0x101);
+        assert_eq!(cpu.cycles.machine(), 11);
+    }
+
+    #[test]
+    fn halt_wakes_without_ime() {
+        // HALT, INC A
+        let (mut cpu, mut bus) = cpu_with_program(&[0x76, 0x3C]);
+        bus.interrupts.enable = Interrupt::Timer as u8;
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        bus.interrupts.request(Interrupt::Timer);
+        cpu.step(&mut bus);
+        assert!(!cpu.is_halted);
+        assert_eq!(cpu.registers.a, 1);
+        assert_eq!(cpu.registers.pc, 0x102);
+    }
+
+    #[test]
+    fn halt_wakes_into_dispatch_with_ime() {
+        let (mut cpu, mut bus) = cpu_with_program(&[0x76]);
+        cpu.ime = true;
+        cpu.registers.sp = 0xD000;
+        bus.interrupts.enable = Interrupt::VBlank as u8;
+        cpu.step(&mut bus);
+        bus.interrupts.request(Interrupt::VBlank);
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(cpu.registers.pc, 0x40);
+    }
+
+    #[test]
+    fn halt_bug_reads_next_byte_twice() {
+        // HALT, INC A, NOP
+        let (mut cpu, mut bus) = cpu_with_program(&[0x76, 0x3C, 0x00]);
+        bus.interrupts.enable = Interrupt::Timer as u8;
+        bus.interrupts.request(Interrupt::Timer);
+        cpu.step(&mut bus);
+        assert!(!cpu.is_halted);
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.registers.a, 2);
+        assert_eq!(cpu.registers.pc, 0x102);
+    }
+
+    #[test]
+    fn stop_waits_for_joypad() {
+        // STOP, 0x00, INC A
+        let (mut cpu, mut bus) = cpu_with_program(&[0x10, 0x00, 0x3C]);
+        bus.timer.set_counter(0xAB00);
+        cpu.step(&mut bus);
+        assert!(cpu.is_stopped);
+        assert_eq!(bus.timer.read(DIV), 0);
+        assert_eq!(cpu.step(&mut bus), 0);
+        bus.joypad.press(Button::Start, &mut bus.interrupts);
+        cpu.step(&mut bus);
+        assert!(!cpu.is_stopped);
+        assert_eq!(cpu.registers.a, 1);
+    }
+
+    #[test]
+    fn stop_ignores_a_stale_joypad_interrupt() {
+        // STOP, 0x00, INC A with both rows selected
+        let (mut cpu, mut bus) = cpu_with_program(&[0x10, 0x00, 0x3C]);
+        bus.interrupts.request(Interrupt::Joypad);
+        cpu.step(&mut bus);
+        assert!(cpu.is_stopped);
+        assert_eq!(cpu.step(&mut bus), 0);
+        assert!(cpu.is_stopped);
+
+        bus.joypad.press(Button::A, &mut bus.interrupts);
+        cpu.step(&mut bus);
+        assert!(!cpu.is_stopped);
+        assert_eq!(cpu.registers.a, 1);
+    }
+
+    #[test]
+    fn base_instruction_timing() {
+        for f in [0, Z | C] {
+            for opcode in 0..=0xFFu8 {
+                if CYCLES[opcode as usize] == 0 {
+                    continue;
                 }
+                let expected = CYCLES[opcode as usize] + taken_cycles(opcode, f);
+                assert_eq!(run(&[opcode], f), expected, "opcode 0x{:02X} with F={:02X}", opcode, f);
             }
-            Instruction::UNKNOWN(opcode) => println!("UNKNOWN OPCODE: 0x{:X}", opcode),
-            _ => unimplemented!("{:?}", instruction)
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn prefixed_instruction_timing() {
+        for opcode in 0..=0xFFu8 {
+            let expected = match (opcode & 0x07, opcode) {
+                (6, 0x40..=0x7F) => 3,
+                (6, _) => 4,
+                _ => 2,
+            };
+            assert_eq!(run(&[0xCB, opcode], 0), expected, "opcode 0xCB 0x{:02X}", opcode);
+        }
+    }
+}
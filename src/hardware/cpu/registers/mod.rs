@@ -71,6 +71,22 @@ impl Registers {
         self.c = (value & 0xff) as u8
     }
 
+    pub fn set_de(&mut self, value: u16) {
+        self.d = (value >> 8) as u8;
+        self.e = (value & 0xff) as u8
+    }
+
+    // the lower nibble of F is hardwired to zero
+    pub fn set_af(&mut self, value: u16) {
+        self.a = (value >> 8) as u8;
+        self.f = (value & 0xf0) as u8
+    }
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
@@ -83,11 +99,11 @@ mod tests {
         let mut registers = Registers::new();
 
         registers.set_flag(Flag::Zero, true);
-        assert_eq!(registers.f, 0b0001_0000);
+        assert_eq!(registers.f, 0b1000_0000);
         registers.set_flag(Flag::HalfCarry, true);
-        assert_eq!(registers.f, 0b0101_0000);
+        assert_eq!(registers.f, 0b1010_0000);
 
         registers.set_flag(Flag::HalfCarry, false);
-        assert_eq!(registers.f, 0b0001_0000);
+        assert_eq!(registers.f, 0b1000_0000);
     }
 }
\ No newline at end of file
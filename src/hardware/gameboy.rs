@@ -0,0 +1,139 @@
+use crate::hardware::boot_rom::BootROM;
+use crate::hardware::cartridge::Cartridge;
+use crate::hardware::cpu::bus::SystemBus;
+use crate::hardware::cpu::CPU;
+use crate::hardware::joypad::Button;
+use crate::hardware::model::Model;
+
+// 154 lines of 456 dots, in M-cycles
+pub const CYCLES_PER_FRAME: usize = 154 * 456 / 4;
+
+// the whole console, what frontends and test harnesses drive, the CPU runs on the bus one step
+// at a time
+pub struct GameBoy {
+    cpu: CPU,
+    bus: SystemBus,
+}
+
+impl GameBoy {
+    pub fn new(cartridge: Cartridge) -> Self {
+        GameBoy::with_model(cartridge, Model::default())
+    }
+
+    // starts where the model's boot ROM would have handed over
+    pub fn with_model(cartridge: Cartridge, model: Model) -> Self {
+        let mut cpu = CPU::new();
+        cpu.registers = model.registers(&cartridge.header);
+        GameBoy { cpu, bus: SystemBus::with_model(cartridge, model) }
+    }
+
+    // starts from power on at 0x0000 and leaves the setup to the boot ROM
+    pub fn with_boot_rom(cartridge: Cartridge, boot_rom: BootROM) -> Self {
+        let mut cpu = CPU::new();
+        cpu.registers.pc = 0x0000;
+        GameBoy { cpu, bus: SystemBus::with_boot_rom(cartridge, boot_rom) }
+    }
+
+    // for debuggers, changes go through the methods below
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
+    pub fn bus(&self) -> &SystemBus {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut SystemBus {
+        &mut self.bus
+    }
+
+    pub fn cartridge(&self) -> &Cartridge {
+        &self.bus.cartridge
+    }
+
+    pub fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.bus.cartridge
+    }
+
+    // executes one instruction and returns how many M-cycles it took, 0 while stopped
+    pub fn step_instruction(&mut self) -> usize {
+        self.cpu.step(&mut self.bus)
+    }
+
+    // runs whole instructions until at least cycles M-cycles have passed and returns how many did,
+    // fewer when the CPU is stopped or has hit an illegal opcode
+    pub fn run_cycles(&mut self, cycles: usize) -> usize {
+        let mut elapsed = 0;
+        while self.cpu.is_running && elapsed < cycles {
+            let step = self.cpu.step(&mut self.bus);
+            if step == 0 {
+                break;
+            }
+            elapsed += step;
+        }
+        elapsed
+    }
+
+    // runs until the PPU finishes a frame, or a frame's worth of time when the LCD is off, and
+    // returns the M-cycles it took
+    pub fn run_frame(&mut self) -> usize {
+        let frame = self.bus.ppu.frame_count();
+        let mut elapsed = 0;
+        while self.cpu.is_running && self.bus.ppu.frame_count() == frame && elapsed < CYCLES_PER_FRAME {
+            let step = self.cpu.step(&mut self.bus);
+            // STOP only ends on a button press, which can't come from in here
+            if step == 0 {
+                break;
+            }
+            elapsed += step;
+        }
+        elapsed
+    }
+
+    // the last completed frame, one shade (0-3) per pixel, row by row
+    pub fn framebuffer(&self) -> &[u8] {
+        self.bus.ppu.framebuffer()
+    }
+
+    // interleaved stereo samples produced since the last call
+    pub fn audio_samples(&mut self) -> Vec<f32> {
+        self.bus.apu.drain_samples()
+    }
+
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        if pressed {
+            self.bus.joypad.press(button, &mut self.bus.interrupts);
+        } else {
+            self.bus.joypad.release(button);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cartridge::Cartridge;
+    use crate::hardware::gameboy::{CYCLES_PER_FRAME, GameBoy};
+    use crate::hardware::joypad::Button;
+
+    #[test]
+    fn run_frame_completes_a_frame() {
+        let mut gameboy = GameBoy::new(Cartridge::blank());
+        let frame = gameboy.bus().ppu.frame_count();
+        let elapsed = gameboy.run_frame();
+        assert_eq!(gameboy.bus().ppu.frame_count(), frame + 1);
+        assert!(elapsed <= CYCLES_PER_FRAME);
+    }
+
+    #[test]
+    fn run_frame_returns_while_stopped() {
+        // STOP, 0x00, then NOPs
+        let mut gameboy = GameBoy::new(Cartridge::with_program(0x100, &[0x10, 0x00]));
+        gameboy.run_frame();
+        assert!(gameboy.cpu().is_stopped);
+        assert_eq!(gameboy.run_frame(), 0);
+        assert_eq!(gameboy.run_cycles(100), 0);
+
+        gameboy.set_button(Button::Start, true);
+        assert!(gameboy.run_cycles(100) >= 100);
+    }
+}
@@ -0,0 +1,138 @@
+#[cfg(target_family = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::hardware::cpu::interrupts::{Interrupt, Interrupts};
+
+pub const P1: u16 = 0xFF00;
+
+const SELECT_DIRECTIONS: u8 = 0b0001_0000;
+const SELECT_ACTIONS: u8 = 0b0010_0000;
+
+// the low nibble is the direction row, the high nibble the action row, both in P10-P13 order
+#[cfg_attr(target_family = "wasm", wasm_bindgen)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Button {
+    Right = 0b0000_0001,
+    Left = 0b0000_0010,
+    Up = 0b0000_0100,
+    Down = 0b0000_1000,
+    A = 0b0001_0000,
+    B = 0b0010_0000,
+    Select = 0b0100_0000,
+    Start = 0b1000_0000,
+}
+
+// P1/JOYP: a row is selected by writing 0 to bit 4 or 5, pressed buttons in it then read as 0
+pub struct Joypad {
+    select: u8,
+    pressed: u8,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Joypad {
+            // as left by the boot ROM, P1 reads 0xCF
+            select: 0x00,
+            pressed: 0x00,
+        }
+    }
+
+    // P10-P13, set for every pressed button in a selected row
+    fn lines(&self) -> u8 {
+        let mut lines = 0;
+        if self.select & SELECT_DIRECTIONS == 0 {
+            lines |= self.pressed & 0x0F;
+        }
+        if self.select & SELECT_ACTIONS == 0 {
+            lines |= self.pressed >> 4;
+        }
+        lines
+    }
+
+    // the interrupt fires when any of P10-P13 goes from high to low
+    fn update(&self, before: u8, interrupts: &mut Interrupts) {
+        if self.lines() & !before != 0 {
+            interrupts.request(Interrupt::Joypad);
+        }
+    }
+
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.pressed & button as u8 != 0
+    }
+
+    pub fn press(&mut self, button: Button, interrupts: &mut Interrupts) {
+        let before = self.lines();
+        self.pressed |= button as u8;
+        self.update(before, interrupts);
+    }
+
+    pub fn release(&mut self, button: Button) {
+        self.pressed &= !(button as u8);
+    }
+
+    pub fn read(&self) -> u8 {
+        0xC0 | self.select | (!self.lines() & 0x0F)
+    }
+
+    // selecting a row with a button already held also pulls a line low
+    pub fn write(&mut self, value: u8, interrupts: &mut Interrupts) {
+        let before = self.lines();
+        self.select = value & (SELECT_DIRECTIONS | SELECT_ACTIONS);
+        self.update(before, interrupts);
+    }
+}
+
+impl Default for Joypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cpu::interrupts::{Interrupt, Interrupts};
+    use crate::hardware::joypad::{Button, Joypad};
+
+    #[test]
+    fn reads_selected_row() {
+        let mut joypad = Joypad::new();
+        let mut interrupts = Interrupts::new();
+        joypad.press(Button::Down, &mut interrupts);
+        joypad.press(Button::A, &mut interrupts);
+
+        joypad.write(0x20, &mut interrupts);
+        assert_eq!(joypad.read(), 0xE7);
+        joypad.write(0x10, &mut interrupts);
+        assert_eq!(joypad.read(), 0xDE);
+        joypad.write(0x30, &mut interrupts);
+        assert_eq!(joypad.read(), 0xFF);
+
+        joypad.release(Button::A);
+        joypad.write(0x00, &mut interrupts);
+        assert_eq!(joypad.read(), 0xC7);
+    }
+
+    #[test]
+    fn press_in_selected_row_requests_interrupt() {
+        let mut joypad = Joypad::new();
+        let mut interrupts = Interrupts::new();
+        joypad.write(0x10, &mut interrupts);
+
+        joypad.press(Button::Up, &mut interrupts);
+        assert_eq!(interrupts.flag & Interrupt::Joypad as u8, 0);
+        joypad.press(Button::Start, &mut interrupts);
+        assert_ne!(interrupts.flag & Interrupt::Joypad as u8, 0);
+    }
+
+    #[test]
+    fn selecting_held_row_requests_interrupt() {
+        let mut joypad = Joypad::new();
+        let mut interrupts = Interrupts::new();
+        joypad.write(0x30, &mut interrupts);
+        joypad.press(Button::Left, &mut interrupts);
+        assert_eq!(interrupts.flag & Interrupt::Joypad as u8, 0);
+
+        joypad.write(0x20, &mut interrupts);
+        assert_ne!(interrupts.flag & Interrupt::Joypad as u8, 0);
+    }
+}
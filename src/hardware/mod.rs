@@ -1,3 +1,11 @@
+pub mod apu;
+pub mod boot_rom;
 pub mod cartridge;
 pub mod cpu;
+pub mod gameboy;
+pub mod joypad;
+pub mod model;
+pub mod ppu;
+pub mod serial;
+pub mod timer;
 pub mod utils;
\ No newline at end of file
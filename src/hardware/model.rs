@@ -0,0 +1,201 @@
+use crate::hardware::apu::{NR11, NR12, NR13, NR14};
+use crate::hardware::cartridge::header::{CartridgeHeader, CGBSupport};
+use crate::hardware::cpu::registers::flags::Flag;
+use crate::hardware::cpu::registers::Registers;
+use crate::hardware::joypad::P1;
+use crate::hardware::serial::SC;
+
+// channel 1 as the chime leaves it, its last note still fading out
+const CHIME: [(u16, u8); 4] = [(NR11, 0x80), (NR12, 0xF3), (NR13, 0xC1), (NR14, 0x87)];
+
+// the console being emulated, without a boot ROM it decides the state the CPU starts in
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Model {
+    // the first revision of the original Game Boy
+    DMG0,
+    #[default]
+    DMG,
+    // Game Boy Pocket and Light
+    MGB,
+    SGB,
+    SGB2,
+    CGB,
+    // a Game Boy Advance running Game Boy software
+    AGB,
+}
+
+impl Model {
+    pub fn is_cgb(self) -> bool {
+        matches!(self, Model::CGB | Model::AGB)
+    }
+
+    // the registers at 0x0100 when the boot ROM hands over
+    pub fn registers(self, header: &CartridgeHeader) -> Registers {
+        let mut registers = Registers::new();
+        // the DMG boot ROM leaves H and C set unless the header checksum happens to be 0
+        let checksum_flags = header.header_checksum() != 0;
+        match self {
+            Model::DMG0 => {
+                registers.set_af(0x0100);
+                registers.set_bc(0xFF13);
+                registers.set_de(0x00C1);
+                registers.set_hl(0x8403);
+            }
+            Model::DMG | Model::MGB => {
+                registers.set_af(if self == Model::DMG { 0x0180 } else { 0xFF80 });
+                registers.set_bc(0x0013);
+                registers.set_de(0x00D8);
+                registers.set_hl(0x014D);
+            }
+            Model::SGB | Model::SGB2 => {
+                registers.set_af(if self == Model::SGB { 0x0100 } else { 0xFF00 });
+                registers.set_bc(0x0014);
+                registers.set_de(0x0000);
+                registers.set_hl(0xC060);
+            }
+            Model::CGB | Model::AGB => {
+                registers.set_af(0x1180);
+                if header.cgb_support() == CGBSupport::Unsupported {
+                    // DMG compatibility mode, B is what picked the palette
+                    registers.b = compatibility_palette_id(header);
+                    registers.c = 0x00;
+                    registers.set_de(0x0008);
+                    registers.set_hl(if matches!(registers.b, 0x43 | 0x58) { 0x991A } else { 0x007C });
+                } else {
+                    registers.set_bc(0x0000);
+                    registers.set_de(0xFF56);
+                    registers.set_hl(0x000D);
+                }
+                if self == Model::AGB {
+                    // the AGB boot ROM ends with an extra INC B, which also sets the flags
+                    registers.b = registers.b.wrapping_add(1);
+                    registers.set_flag(Flag::Zero, registers.b == 0);
+                    registers.set_flag(Flag::Negative, false);
+                    registers.set_flag(Flag::HalfCarry, registers.b & 0x0F == 0);
+                }
+            }
+        }
+        if matches!(self, Model::DMG | Model::MGB) {
+            registers.set_flag(Flag::HalfCarry, checksum_flags);
+            registers.set_flag(Flag::Carry, checksum_flags);
+        }
+        registers.sp = 0xFFFE;
+        registers.pc = 0x0100;
+        registers
+    }
+
+    // the timer's internal counter at 0x0100, DIV is its upper byte
+    pub fn divider(self) -> u16 {
+        match self {
+            Model::DMG0 => 0x1830,
+            Model::DMG | Model::MGB => 0xABCC,
+            // the SGB boot ROM's run time depends on the SNES side, so there is no fixed value
+            Model::SGB | Model::SGB2 => 0x0000,
+            Model::CGB | Model::AGB => 0x1EA0,
+        }
+    }
+
+    // writes that take the I/O registers from power on to where the boot ROM leaves them, on top
+    // of what every model has in common
+    pub fn io_registers(self) -> &'static [(u16, u8)] {
+        const SGB: [(u16, u8); 3] = [(NR11, 0x80), (NR12, 0xF3), (P1, 0x30)];
+        const CGB: [(u16, u8); 5] = [CHIME[0], CHIME[1], CHIME[2], CHIME[3], (SC, 0x7F)];
+        match self {
+            Model::DMG0 | Model::DMG | Model::MGB => &CHIME,
+            // the SNES plays the chime instead and the boot ROM leaves both joypad rows deselected
+            Model::SGB | Model::SGB2 => &SGB,
+            // bit 1 of SC, the CGB's fast serial clock, reads as set
+            Model::CGB | Model::AGB => &CGB,
+        }
+    }
+}
+
+// the sum of the title bytes for Nintendo published games, 0 otherwise
+fn compatibility_palette_id(header: &CartridgeHeader) -> u8 {
+    let nintendo = match header.old_licensee_code() {
+        0x33 => header.new_licensee_code() == *b"01",
+        code => code == 0x01,
+    };
+    if !nintendo {
+        return 0;
+    }
+    header.title_bytes().iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::apu::{NR12, NR52};
+    use crate::hardware::cartridge::Cartridge;
+    use crate::hardware::cartridge::header::CartridgeHeader;
+    use crate::hardware::cpu::interrupts::Interrupt;
+    use crate::hardware::gameboy::GameBoy;
+    use crate::hardware::joypad::P1;
+    use crate::hardware::model::Model;
+    use crate::hardware::serial::SC;
+    use crate::hardware::timer::DIV;
+
+    fn header(patch: &[(usize, u8)]) -> CartridgeHeader {
+        let mut rom = vec![0; 0x8000];
+        for &(address, value) in patch {
+            rom[address] = value;
+        }
+        CartridgeHeader::from_rom(&rom).unwrap()
+    }
+
+    #[test]
+    fn dmg_registers() {
+        let registers = Model::DMG.registers(&header(&[(0x14D, 0x3A)]));
+        assert_eq!(registers.get_af(), 0x01B0);
+        assert_eq!(registers.get_bc(), 0x0013);
+        assert_eq!(registers.get_de(), 0x00D8);
+        assert_eq!(registers.get_hl(), 0x014D);
+        assert_eq!(registers.sp, 0xFFFE);
+        assert_eq!(registers.pc, 0x0100);
+
+        // a zero header checksum leaves H and C clear
+        assert_eq!(Model::DMG.registers(&header(&[])).get_af(), 0x0180);
+
+        // DMG0 leaves the flags clear whatever the checksum
+        let registers = Model::DMG0.registers(&header(&[(0x14D, 0x3A)]));
+        assert_eq!(registers.get_af(), 0x0100);
+        assert_eq!(registers.get_bc(), 0xFF13);
+        assert_eq!(registers.get_hl(), 0x8403);
+    }
+
+    #[test]
+    fn cgb_registers() {
+        let cgb = header(&[(0x143, 0x80)]);
+        assert_eq!(Model::CGB.registers(&cgb).get_af(), 0x1180);
+        assert_eq!(Model::CGB.registers(&cgb).get_de(), 0xFF56);
+        assert_eq!(Model::AGB.registers(&cgb).get_bc(), 0x0100);
+        assert_eq!(Model::AGB.registers(&cgb).get_af(), 0x1100);
+
+        // Nintendo's DMG games get the title checksum in B
+        let dmg = header(&[(0x134, 0x20), (0x135, 0x23), (0x14B, 0x01)]);
+        let registers = Model::CGB.registers(&dmg);
+        assert_eq!(registers.get_bc(), 0x4300);
+        assert_eq!(registers.get_hl(), 0x991A);
+    }
+
+    #[test]
+    fn cpu_starts_in_post_boot_state() {
+        let gameboy = GameBoy::with_model(Cartridge::blank(), Model::DMG);
+        assert_eq!(gameboy.bus().timer.read(DIV), 0xAB);
+        assert_eq!(gameboy.bus().interrupts.read_flag(), 0xE0 | Interrupt::VBlank as u8);
+
+        assert_eq!(gameboy.bus().read(NR52), 0xF1);
+        assert_eq!(gameboy.bus().read(NR12), 0xF3);
+        assert_eq!(gameboy.bus().read(P1), 0xCF);
+        assert_eq!(gameboy.bus().read(SC), 0x7E);
+
+        let gameboy = GameBoy::with_model(Cartridge::blank(), Model::SGB);
+        assert_eq!(gameboy.bus().read(NR52), 0xF0);
+        assert_eq!(gameboy.bus().read(NR12), 0xF3);
+        assert_eq!(gameboy.bus().read(P1), 0xFF);
+
+        let gameboy = GameBoy::with_model(Cartridge::blank(), Model::CGB);
+        assert_eq!(gameboy.cpu().registers.a, 0x11);
+        assert_eq!(gameboy.bus().read(NR52), 0xF1);
+        assert_eq!(gameboy.bus().read(SC), 0x7F);
+    }
+}
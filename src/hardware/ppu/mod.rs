@@ -0,0 +1,456 @@
+use crate::hardware::cpu::interrupts::{Interrupt, Interrupts};
+
+pub mod render;
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+pub const VRAM_SIZE: usize = 0x2000;
+pub const OAM_SIZE: usize = 0xA0;
+
+// RGB for the four DMG shades, lightest first
+pub const DMG_PALETTE: [[u8; 3]; 4] = [
+    [0xE0, 0xF8, 0xD0],
+    [0x88, 0xC0, 0x70],
+    [0x34, 0x68, 0x56],
+    [0x08, 0x18, 0x20],
+];
+
+pub const LCDC: u16 = 0xFF40;
+pub const STAT: u16 = 0xFF41;
+pub const SCY: u16 = 0xFF42;
+pub const SCX: u16 = 0xFF43;
+pub const LY: u16 = 0xFF44;
+pub const LYC: u16 = 0xFF45;
+pub const BGP: u16 = 0xFF47;
+pub const OBP0: u16 = 0xFF48;
+pub const OBP1: u16 = 0xFF49;
+pub const WY: u16 = 0xFF4A;
+pub const WX: u16 = 0xFF4B;
+
+const DOTS_PER_LINE: u16 = 456;
+const OAM_SCAN_DOTS: u16 = 80;
+const VBLANK_LINE: u8 = 144;
+const LINES_PER_FRAME: u8 = 154;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OAMScan = 2,
+    Drawing = 3,
+}
+
+pub enum LCDCFlag {
+    BackgroundEnable = 0b0000_0001,
+    ObjectEnable = 0b0000_0010,
+    ObjectSize = 0b0000_0100,
+    BackgroundTileMap = 0b0000_1000,
+    TileData = 0b0001_0000,
+    WindowEnable = 0b0010_0000,
+    WindowTileMap = 0b0100_0000,
+    LCDEnable = 0b1000_0000,
+}
+
+pub enum STATFlag {
+    HBlankInterrupt = 0b0000_1000,
+    VBlankInterrupt = 0b0001_0000,
+    OAMInterrupt = 0b0010_0000,
+    LYCInterrupt = 0b0100_0000,
+}
+
+pub struct PPU {
+    pub vram: Vec<u8>,
+    pub oam: Vec<u8>,
+    pub lcdc: u8,
+    // only the interrupt select bits, mode and coincidence are computed on read
+    stat: u8,
+    pub scy: u8,
+    pub scx: u8,
+    pub ly: u8,
+    pub lyc: u8,
+    pub bgp: u8,
+    pub obp0: u8,
+    pub obp1: u8,
+    pub wy: u8,
+    pub wx: u8,
+    mode: Mode,
+    // position within the current line
+    dot: u16,
+    drawing_dots: u16,
+    // set once LY has matched WY during the frame
+    window_triggered: bool,
+    // the line of the window to draw next, only advances on lines that show it
+    window_line: u8,
+    // STAT interrupts fire on the rising edge of the OR of all enabled sources
+    stat_line: bool,
+    // OAM indices picked during OAM scan for the current line
+    line_objects: Vec<usize>,
+    buffer: Vec<u8>,
+    frame: Vec<u8>,
+    frame_count: usize,
+}
+
+impl PPU {
+    pub fn new() -> Self {
+        PPU {
+            vram: vec![0; VRAM_SIZE],
+            oam: vec![0; OAM_SIZE],
+            // as left by the boot ROM
+            lcdc: 0x91,
+            stat: 0x00,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0xFC,
+            obp0: 0xFF,
+            obp1: 0xFF,
+            wy: 0,
+            wx: 0,
+            mode: Mode::OAMScan,
+            dot: 0,
+            drawing_dots: 172,
+            window_triggered: false,
+            window_line: 0,
+            stat_line: false,
+            line_objects: Vec::with_capacity(10),
+            buffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame_count: 0,
+        }
+    }
+
+    // the last completed frame, one shade (0-3) per pixel, row by row
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.frame
+    }
+
+    // how many frames have been completed, frontends redraw when it changes
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn is_lcd_enabled(&self) -> bool {
+        self.lcdc & LCDCFlag::LCDEnable as u8 != 0
+    }
+
+    // advances one M-cycle, four dots
+    pub fn tick(&mut self, interrupts: &mut Interrupts) {
+        if !self.is_lcd_enabled() {
+            return;
+        }
+        for _ in 0..4 {
+            self.tick_dot(interrupts);
+        }
+    }
+
+    fn tick_dot(&mut self, interrupts: &mut Interrupts) {
+        self.dot += 1;
+        match self.mode {
+            Mode::OAMScan => {
+                if self.dot == OAM_SCAN_DOTS {
+                    self.scan_oam();
+                    if self.ly == self.wy {
+                        self.window_triggered = true;
+                    }
+                    self.drawing_dots = self.drawing_length();
+                    self.render_line();
+                    self.mode = Mode::Drawing;
+                }
+            }
+            Mode::Drawing => {
+                if self.dot == OAM_SCAN_DOTS + self.drawing_dots {
+                    self.mode = Mode::HBlank;
+                }
+            }
+            Mode::HBlank => {
+                if self.dot == DOTS_PER_LINE {
+                    self.dot = 0;
+                    self.ly += 1;
+                    if self.ly == VBLANK_LINE {
+                        self.mode = Mode::VBlank;
+                        std::mem::swap(&mut self.frame, &mut self.buffer);
+                        self.frame_count += 1;
+                        interrupts.request(Interrupt::VBlank);
+                    } else {
+                        self.mode = Mode::OAMScan;
+                    }
+                }
+            }
+            Mode::VBlank => {
+                if self.dot == DOTS_PER_LINE {
+                    self.dot = 0;
+                    self.ly += 1;
+                    if self.ly == LINES_PER_FRAME {
+                        self.ly = 0;
+                        self.window_line = 0;
+                        self.window_triggered = false;
+                        self.mode = Mode::OAMScan;
+                    }
+                }
+            }
+        }
+        self.update_stat_line(interrupts);
+    }
+
+    fn update_stat_line(&mut self, interrupts: &mut Interrupts) {
+        let line = match self.mode {
+            Mode::HBlank => self.stat & STATFlag::HBlankInterrupt as u8 != 0,
+            Mode::VBlank => self.stat & STATFlag::VBlankInterrupt as u8 != 0,
+            Mode::OAMScan => self.stat & STATFlag::OAMInterrupt as u8 != 0,
+            Mode::Drawing => false,
+        } || (self.ly == self.lyc && self.stat & STATFlag::LYCInterrupt as u8 != 0);
+
+        if line && !self.stat_line {
+            interrupts.request(Interrupt::LCDStat);
+        }
+        self.stat_line = line;
+    }
+
+    // the first 10 objects in OAM order that overlap the current line
+    fn scan_oam(&mut self) {
+        let height = self.object_height();
+        self.line_objects.clear();
+        for index in 0..40 {
+            let y = self.oam[index * 4] as u16;
+            let line = self.ly as u16 + 16;
+            if line >= y && line < y + height as u16 {
+                self.line_objects.push(index);
+                if self.line_objects.len() == 10 {
+                    break;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn object_height(&self) -> u8 {
+        if self.lcdc & LCDCFlag::ObjectSize as u8 != 0 { 16 } else { 8 }
+    }
+
+    pub(crate) fn is_window_visible(&self) -> bool {
+        self.lcdc & LCDCFlag::WindowEnable as u8 != 0
+            && self.lcdc & LCDCFlag::BackgroundEnable as u8 != 0
+            && self.window_triggered
+            && self.wx <= 166
+    }
+
+    // mode 3 takes 172 dots plus the penalties for fine scrolling, the window and objects
+    fn drawing_length(&self) -> u16 {
+        let mut dots = 172 + (self.scx % 8) as u16;
+        if self.is_window_visible() {
+            dots += 6;
+        }
+        if self.lcdc & LCDCFlag::ObjectEnable as u8 != 0 {
+            let mut fetched_tiles: Vec<u16> = Vec::with_capacity(10);
+            let mut objects: Vec<u8> = self.line_objects.iter().map(|&index| self.oam[index * 4 + 1]).collect();
+            objects.sort_unstable();
+            for x in objects {
+                if x == 0 {
+                    dots += 11;
+                    continue;
+                }
+                dots += 6;
+                // the first object in a background tile also waits for that tile's fetch
+                let pixel = x as u16 + self.scx as u16 % 8;
+                let tile = pixel / 8;
+                if !fetched_tiles.contains(&tile) {
+                    fetched_tiles.push(tile);
+                    dots += 5u16.saturating_sub(pixel % 8);
+                }
+            }
+        }
+        dots
+    }
+
+    pub fn read_vram(&self, address: u16) -> u8 {
+        if self.is_lcd_enabled() && self.mode == Mode::Drawing {
+            return 0xFF;
+        }
+        self.vram[(address & 0x1FFF) as usize]
+    }
+
+    pub fn write_vram(&mut self, address: u16, value: u8) {
+        if self.is_lcd_enabled() && self.mode == Mode::Drawing {
+            return;
+        }
+        self.vram[(address & 0x1FFF) as usize] = value;
+    }
+
+    pub fn is_oam_accessible(&self) -> bool {
+        !self.is_lcd_enabled() || matches!(self.mode, Mode::HBlank | Mode::VBlank)
+    }
+
+    pub fn read_oam(&self, address: u16) -> u8 {
+        if !self.is_oam_accessible() {
+            return 0xFF;
+        }
+        self.oam[(address - 0xFE00) as usize]
+    }
+
+    pub fn write_oam(&mut self, address: u16, value: u8) {
+        if !self.is_oam_accessible() {
+            return;
+        }
+        self.oam[(address - 0xFE00) as usize] = value;
+    }
+
+    pub fn read_register(&self, address: u16) -> u8 {
+        match address {
+            LCDC => self.lcdc,
+            STAT => {
+                let coincidence = if self.ly == self.lyc { 0b0000_0100 } else { 0 };
+                let mode = if self.is_lcd_enabled() { self.mode as u8 } else { 0 };
+                0x80 | self.stat | coincidence | mode
+            }
+            SCY => self.scy,
+            SCX => self.scx,
+            LY => self.ly,
+            LYC => self.lyc,
+            BGP => self.bgp,
+            OBP0 => self.obp0,
+            OBP1 => self.obp1,
+            WY => self.wy,
+            WX => self.wx,
+            _ => unreachable!("0x{:04X} is not a PPU register", address),
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8, interrupts: &mut Interrupts) {
+        match address {
+            LCDC => {
+                let was_enabled = self.is_lcd_enabled();
+                self.lcdc = value;
+                if was_enabled && !self.is_lcd_enabled() {
+                    self.turn_off();
+                }
+            }
+            STAT => self.stat = value & 0b0111_1000,
+            SCY => self.scy = value,
+            SCX => self.scx = value,
+            // LY is read-only
+            LY => {}
+            LYC => self.lyc = value,
+            BGP => self.bgp = value,
+            OBP0 => self.obp0 = value,
+            OBP1 => self.obp1 = value,
+            WY => self.wy = value,
+            WX => self.wx = value,
+            _ => unreachable!("0x{:04X} is not a PPU register", address),
+        }
+        if self.is_lcd_enabled() {
+            self.update_stat_line(interrupts);
+        }
+    }
+
+    // the screen goes blank and the PPU restarts from the top of the frame when turned back on
+    fn turn_off(&mut self) {
+        self.ly = 0;
+        self.dot = 0;
+        self.mode = Mode::OAMScan;
+        self.window_line = 0;
+        self.window_triggered = false;
+        self.stat_line = false;
+        self.frame.iter_mut().for_each(|pixel| *pixel = 0);
+        self.frame_count += 1;
+    }
+}
+
+impl Default for PPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cpu::interrupts::{Interrupt, Interrupts};
+    use crate::hardware::ppu::{Mode, PPU, STAT, STATFlag};
+
+    // advances the PPU by dots, in whole M-cycles of 4 dots
+    fn run_dots(ppu: &mut PPU, interrupts: &mut Interrupts, dots: usize) {
+        for _ in 0..dots / 4 {
+            ppu.tick(interrupts);
+        }
+    }
+
+    #[test]
+    fn line_timing() {
+        let mut ppu = PPU::new();
+        let mut interrupts = Interrupts::new();
+
+        run_dots(&mut ppu, &mut interrupts, 76);
+        assert_eq!(ppu.mode(), Mode::OAMScan);
+        run_dots(&mut ppu, &mut interrupts, 4);
+        assert_eq!(ppu.mode(), Mode::Drawing);
+        run_dots(&mut ppu, &mut interrupts, 172);
+        assert_eq!(ppu.mode(), Mode::HBlank);
+        run_dots(&mut ppu, &mut interrupts, 456 - 252);
+        assert_eq!(ppu.ly, 1);
+        assert_eq!(ppu.mode(), Mode::OAMScan);
+    }
+
+    #[test]
+    fn frame_timing_and_vblank_interrupt() {
+        let mut ppu = PPU::new();
+        let mut interrupts = Interrupts::new();
+
+        run_dots(&mut ppu, &mut interrupts, 456 * 144 - 4);
+        assert_eq!(interrupts.flag & Interrupt::VBlank as u8, 0);
+        run_dots(&mut ppu, &mut interrupts, 4);
+        assert_eq!(ppu.mode(), Mode::VBlank);
+        assert_eq!(ppu.frame_count(), 1);
+        assert_ne!(interrupts.flag & Interrupt::VBlank as u8, 0);
+
+        run_dots(&mut ppu, &mut interrupts, 456 * 10);
+        assert_eq!(ppu.ly, 0);
+        assert_eq!(ppu.mode(), Mode::OAMScan);
+    }
+
+    #[test]
+    fn lyc_stat_interrupt() {
+        let mut ppu = PPU::new();
+        let mut interrupts = Interrupts::new();
+        ppu.lyc = 2;
+        ppu.write_register(STAT, STATFlag::LYCInterrupt as u8, &mut interrupts);
+
+        run_dots(&mut ppu, &mut interrupts, 456 * 2 - 4);
+        assert_eq!(interrupts.flag & Interrupt::LCDStat as u8, 0);
+        run_dots(&mut ppu, &mut interrupts, 4);
+        assert_ne!(interrupts.flag & Interrupt::LCDStat as u8, 0);
+        assert_eq!(ppu.read_register(STAT) & 0b0000_0100, 0b0000_0100);
+    }
+
+    #[test]
+    fn background_tile_is_drawn() {
+        let mut ppu = PPU::new();
+        let mut interrupts = Interrupts::new();
+        // tile 1: first row uses colors 3, 2, 1, 0, 0, 0, 0, 0
+        ppu.vram[0x10] = 0b1010_0000;
+        ppu.vram[0x11] = 0b1100_0000;
+        ppu.vram[0x1800] = 0x01;
+        ppu.bgp = 0b1110_0100;
+        ppu.lcdc = 0x91;
+
+        run_dots(&mut ppu, &mut interrupts, 456 * 144);
+        assert_eq!(&ppu.framebuffer()[0..5], &[3, 2, 1, 0, 0]);
+    }
+
+    #[test]
+    fn object_over_background() {
+        let mut ppu = PPU::new();
+        let mut interrupts = Interrupts::new();
+        // tile 1 is a solid row of color 1
+        ppu.vram[0x10] = 0xFF;
+        ppu.oam[0..4].copy_from_slice(&[16, 8 + 4, 1, 0]);
+        ppu.obp0 = 0b1110_0100;
+        ppu.lcdc = 0x93;
+
+        run_dots(&mut ppu, &mut interrupts, 456 * 144);
+        assert_eq!(&ppu.framebuffer()[2..6], &[0, 0, 1, 1]);
+        assert_eq!(ppu.framebuffer()[12], 0);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::hardware::ppu::{LCDCFlag, PPU, SCREEN_WIDTH};
+
+pub enum ObjectFlag {
+    Palette = 0b0001_0000,
+    FlipX = 0b0010_0000,
+    FlipY = 0b0100_0000,
+    BehindBackground = 0b1000_0000,
+}
+
+impl PPU {
+    // draws the current line into the back buffer
+    pub(crate) fn render_line(&mut self) {
+        // with the background off the line is blank, and objects always win over it
+        let mut background = [0u8; SCREEN_WIDTH];
+        let palette = if self.lcdc & LCDCFlag::BackgroundEnable as u8 != 0 {
+            self.render_background(&mut background);
+            self.bgp
+        } else {
+            0
+        };
+
+        let row = self.ly as usize * SCREEN_WIDTH;
+        for (x, &color) in background.iter().enumerate() {
+            self.buffer[row + x] = Self::shade(palette, color);
+        }
+
+        if self.lcdc & LCDCFlag::ObjectEnable as u8 != 0 {
+            self.render_objects(&background);
+        }
+    }
+
+    fn render_background(&mut self, line: &mut [u8; SCREEN_WIDTH]) {
+        let window = self.is_window_visible();
+        let background_map = if self.lcdc & LCDCFlag::BackgroundTileMap as u8 != 0 { 0x1C00 } else { 0x1800 };
+        let window_map = if self.lcdc & LCDCFlag::WindowTileMap as u8 != 0 { 0x1C00 } else { 0x1800 };
+        let mut window_drawn = false;
+
+        for (x, pixel) in line.iter_mut().enumerate() {
+            let (map, map_x, map_y) = if window && x as u16 + 7 >= self.wx as u16 {
+                window_drawn = true;
+                (window_map, (x as u16 + 7 - self.wx as u16) as u8, self.window_line)
+            } else {
+                (background_map, self.scx.wrapping_add(x as u8), self.scy.wrapping_add(self.ly))
+            };
+
+            let tile_index = self.vram[map + (map_y as usize / 8) * 32 + map_x as usize / 8];
+            let address = self.tile_address(tile_index);
+            *pixel = self.tile_pixel(address, map_x % 8, map_y % 8);
+        }
+
+        if window_drawn {
+            self.window_line += 1;
+        }
+    }
+
+    // objects are drawn by priority: lower X first, then lower OAM index, the first opaque pixel wins
+    fn render_objects(&mut self, background: &[u8; SCREEN_WIDTH]) {
+        let height = self.object_height();
+        let mut objects = self.line_objects.clone();
+        objects.sort_by_key(|&index| (self.oam[index * 4 + 1], index));
+
+        let row = self.ly as usize * SCREEN_WIDTH;
+        for (x, &background_color) in background.iter().enumerate() {
+            let screen_x = x as u16 + 8;
+            for &index in &objects {
+                let entry = &self.oam[index * 4..index * 4 + 4];
+                let (object_y, object_x, mut tile, flags) = (entry[0], entry[1] as u16, entry[2], entry[3]);
+                if screen_x < object_x || screen_x >= object_x + 8 {
+                    continue;
+                }
+
+                let mut pixel_x = (screen_x - object_x) as u8;
+                let mut pixel_y = self.ly + 16 - object_y;
+                if flags & ObjectFlag::FlipX as u8 != 0 {
+                    pixel_x = 7 - pixel_x;
+                }
+                if flags & ObjectFlag::FlipY as u8 != 0 {
+                    pixel_y = height - 1 - pixel_y;
+                }
+                if height == 16 {
+                    tile &= 0xFE;
+                }
+
+                // objects always use the 0x8000 addressing mode
+                let address = tile as usize * 16 + (pixel_y as usize / 8) * 16;
+                let color = self.tile_pixel(address, pixel_x, pixel_y % 8);
+                if color == 0 {
+                    continue;
+                }
+
+                if flags & ObjectFlag::BehindBackground as u8 == 0 || background_color == 0 {
+                    let palette = if flags & ObjectFlag::Palette as u8 != 0 { self.obp1 } else { self.obp0 };
+                    self.buffer[row + x] = Self::shade(palette, color);
+                }
+                break;
+            }
+        }
+    }
+
+    // offset into VRAM of a background or window tile
+    fn tile_address(&self, tile_index: u8) -> usize {
+        if self.lcdc & LCDCFlag::TileData as u8 != 0 {
+            tile_index as usize * 16
+        } else {
+            (0x1000 + (tile_index as i8 as i32) * 16) as usize
+        }
+    }
+
+    // the 2-bit color index at (x, y) of the tile starting at address
+    fn tile_pixel(&self, address: usize, x: u8, y: u8) -> u8 {
+        let low = self.vram[address + y as usize * 2];
+        let high = self.vram[address + y as usize * 2 + 1];
+        let bit = 7 - x;
+        (((high >> bit) & 1) << 1) | ((low >> bit) & 1)
+    }
+
+    fn shade(palette: u8, color: u8) -> u8 {
+        (palette >> (color * 2)) & 0b11
+    }
+}
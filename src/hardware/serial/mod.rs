@@ -0,0 +1,179 @@
+use crate::hardware::cpu::interrupts::{Interrupt, Interrupts};
+
+pub const SB: u16 = 0xFF01;
+pub const SC: u16 = 0xFF02;
+
+const TRANSFER_START: u8 = 0b1000_0000;
+const INTERNAL_CLOCK: u8 = 0b0000_0001;
+
+// the internal clock shifts one bit per falling edge of this bit of the timer's counter, 8192 Hz
+const SERIAL_CLOCK_BIT: u16 = 1 << 8;
+
+// whatever is on the other end of the link cable
+pub trait LinkPartner {
+    // called once per transfer with the byte being sent, returns the byte received
+    fn exchange(&mut self, byte: u8) -> u8;
+}
+
+// no cable, the data line floats high
+pub struct Disconnected;
+
+impl LinkPartner for Disconnected {
+    fn exchange(&mut self, _byte: u8) -> u8 {
+        0xFF
+    }
+}
+
+pub struct Serial {
+    data: u8,
+    control: u8,
+    // the partner's byte, shifted into SB as SB is shifted out
+    incoming: u8,
+    // bits of the current transfer still to shift
+    bits: u8,
+    clock_bit: bool,
+    partner: Box<dyn LinkPartner>,
+    // every byte sent, blargg's test ROMs print their results this way
+    output: Vec<u8>,
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Serial {
+            data: 0x00,
+            control: 0x7E,
+            incoming: 0xFF,
+            bits: 0,
+            clock_bit: false,
+            partner: Box::new(Disconnected),
+            output: Vec::new(),
+        }
+    }
+
+    pub fn set_link_partner(&mut self, partner: Box<dyn LinkPartner>) {
+        self.partner = partner;
+    }
+
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    pub fn clear_output(&mut self) {
+        self.output.clear();
+    }
+
+    // advances one M-cycle, divider is the timer's internal counter after this cycle
+    pub fn tick(&mut self, divider: u16, interrupts: &mut Interrupts) {
+        let clock_bit = divider & SERIAL_CLOCK_BIT != 0;
+        let falling_edge = self.clock_bit && !clock_bit;
+        self.clock_bit = clock_bit;
+
+        // with the external clock selected nothing happens until the partner drives the clock
+        if !falling_edge || self.bits == 0 || self.control & INTERNAL_CLOCK == 0 {
+            return;
+        }
+
+        self.data = self.data << 1 | self.incoming >> 7;
+        self.incoming <<= 1;
+        self.bits -= 1;
+        if self.bits == 0 {
+            self.control &= !TRANSFER_START;
+            interrupts.request(Interrupt::Serial);
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            SB => self.data,
+            SC => self.control | 0x7E,
+            _ => unreachable!("0x{:04X} is not a serial register", address),
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            SB => self.data = value,
+            SC => {
+                self.control = value | 0x7E;
+                if value & TRANSFER_START != 0 {
+                    self.output.push(self.data);
+                    self.incoming = self.partner.exchange(self.data);
+                    self.bits = 8;
+                } else {
+                    self.bits = 0;
+                }
+            }
+            _ => unreachable!("0x{:04X} is not a serial register", address),
+        }
+    }
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cpu::interrupts::{Interrupt, Interrupts};
+    use crate::hardware::serial::{LinkPartner, SB, SC, Serial};
+
+    struct Echo;
+
+    impl LinkPartner for Echo {
+        fn exchange(&mut self, byte: u8) -> u8 {
+            byte ^ 0xFF
+        }
+    }
+
+    // ticks until the transfer finishes and returns how many M-cycles it took
+    fn transfer(serial: &mut Serial, interrupts: &mut Interrupts) -> usize {
+        let mut divider: u16 = 0;
+        let mut cycles = 0;
+        while serial.read(SC) & 0x80 != 0 && cycles < 10_000 {
+            divider = divider.wrapping_add(4);
+            serial.tick(divider, interrupts);
+            cycles += 1;
+        }
+        cycles
+    }
+
+    #[test]
+    fn internal_clock_transfer_takes_1024_cycles() {
+        let mut serial = Serial::new();
+        let mut interrupts = Interrupts::new();
+        serial.write(SB, 0x42);
+        serial.write(SC, 0x81);
+
+        assert_eq!(transfer(&mut serial, &mut interrupts), 1024);
+        assert_eq!(serial.read(SB), 0xFF);
+        assert_eq!(serial.read(SC), 0x7F);
+        assert_ne!(interrupts.flag & Interrupt::Serial as u8, 0);
+        assert_eq!(serial.output(), &[0x42]);
+    }
+
+    #[test]
+    fn partner_byte_is_shifted_in() {
+        let mut serial = Serial::new();
+        let mut interrupts = Interrupts::new();
+        serial.set_link_partner(Box::new(Echo));
+        serial.write(SB, 0x0F);
+        serial.write(SC, 0x81);
+
+        transfer(&mut serial, &mut interrupts);
+        assert_eq!(serial.read(SB), 0xF0);
+    }
+
+    #[test]
+    fn external_clock_waits_for_partner() {
+        let mut serial = Serial::new();
+        let mut interrupts = Interrupts::new();
+        serial.write(SB, 0x42);
+        serial.write(SC, 0x80);
+
+        assert_eq!(transfer(&mut serial, &mut interrupts), 10_000);
+        assert_eq!(serial.read(SB), 0x42);
+        assert_eq!(interrupts.flag & Interrupt::Serial as u8, 0);
+    }
+}
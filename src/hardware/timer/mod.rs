@@ -0,0 +1,214 @@
+use crate::hardware::cpu::interrupts::{Interrupt, Interrupts};
+
+pub const DIV: u16 = 0xFF04;
+pub const TIMA: u16 = 0xFF05;
+pub const TMA: u16 = 0xFF06;
+pub const TAC: u16 = 0xFF07;
+
+const TAC_ENABLE: u8 = 0b0000_0100;
+
+pub struct Timer {
+    // DIV is the upper byte of this counter, which advances every T-cycle
+    counter: u16,
+    pub tima: u8,
+    pub tma: u8,
+    tac: u8,
+    // TIMA overflowed during the last M-cycle and reads 0x00 until it is reloaded
+    overflow: bool,
+    // TIMA was reloaded from TMA during the last M-cycle, writes to TIMA are ignored for now
+    reloading: bool,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Timer {
+            counter: 0,
+            tima: 0,
+            tma: 0,
+            tac: 0xF8,
+            overflow: false,
+            reloading: false,
+        }
+    }
+
+    pub fn counter(&self) -> u16 {
+        self.counter
+    }
+
+    pub fn set_counter(&mut self, counter: u16) {
+        self.counter = counter;
+    }
+
+    // the counter bit whose falling edge increments TIMA
+    fn selected_bit(&self) -> u16 {
+        match self.tac & 0b11 {
+            0b00 => 9,
+            0b01 => 3,
+            0b10 => 5,
+            _ => 7,
+        }
+    }
+
+    // TIMA follows the selected bit ANDed with the enable bit, so anything that drops it ticks TIMA
+    fn signal(&self) -> bool {
+        self.tac & TAC_ENABLE != 0 && (self.counter >> self.selected_bit()) & 1 == 1
+    }
+
+    fn increment_tima(&mut self) {
+        let (tima, overflow) = self.tima.overflowing_add(1);
+        self.tima = tima;
+        self.overflow = overflow;
+    }
+
+    // advances one M-cycle, four T-cycles
+    pub fn tick(&mut self, interrupts: &mut Interrupts) {
+        self.reloading = false;
+        if self.overflow {
+            self.overflow = false;
+            self.tima = self.tma;
+            self.reloading = true;
+            interrupts.request(Interrupt::Timer);
+        }
+
+        let before = self.signal();
+        self.counter = self.counter.wrapping_add(4);
+        if before && !self.signal() {
+            self.increment_tima();
+        }
+    }
+
+    // writing DIV or executing STOP clears the whole counter, which can tick TIMA early
+    pub fn reset_divider(&mut self) {
+        let before = self.signal();
+        self.counter = 0;
+        if before {
+            self.increment_tima();
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            DIV => (self.counter >> 8) as u8,
+            TIMA => self.tima,
+            TMA => self.tma,
+            TAC => self.tac | 0xF8,
+            _ => unreachable!("0x{:04X} is not a timer register", address),
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            DIV => self.reset_divider(),
+            TIMA => {
+                // a write in the cycle after the overflow cancels the reload and the interrupt,
+                // a write in the cycle of the reload is overwritten by TMA
+                if !self.reloading {
+                    self.tima = value;
+                    self.overflow = false;
+                }
+            }
+            TMA => {
+                self.tma = value;
+                if self.reloading {
+                    self.tima = value;
+                }
+            }
+            TAC => {
+                let before = self.signal();
+                self.tac = value | 0xF8;
+                if before && !self.signal() {
+                    self.increment_tima();
+                }
+            }
+            _ => unreachable!("0x{:04X} is not a timer register", address),
+        }
+    }
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::cpu::interrupts::{Interrupt, Interrupts};
+    use crate::hardware::timer::{DIV, TAC, Timer, TIMA, TMA};
+
+    fn tick(timer: &mut Timer, interrupts: &mut Interrupts, cycles: usize) {
+        for _ in 0..cycles {
+            timer.tick(interrupts);
+        }
+    }
+
+    #[test]
+    fn div_counts_every_64_cycles() {
+        let mut timer = Timer::new();
+        let mut interrupts = Interrupts::new();
+        tick(&mut timer, &mut interrupts, 63);
+        assert_eq!(timer.read(DIV), 0);
+        tick(&mut timer, &mut interrupts, 1);
+        assert_eq!(timer.read(DIV), 1);
+    }
+
+    #[test]
+    fn tima_counts_at_selected_rate() {
+        let mut timer = Timer::new();
+        let mut interrupts = Interrupts::new();
+        // enabled, every 4 M-cycles
+        timer.write(TAC, 0b101);
+        tick(&mut timer, &mut interrupts, 16);
+        assert_eq!(timer.read(TIMA), 4);
+    }
+
+    #[test]
+    fn overflow_reloads_one_cycle_late() {
+        let mut timer = Timer::new();
+        let mut interrupts = Interrupts::new();
+        timer.write(TAC, 0b101);
+        timer.write(TMA, 0x42);
+        timer.write(TIMA, 0xFF);
+
+        tick(&mut timer, &mut interrupts, 4);
+        assert_eq!(timer.read(TIMA), 0x00);
+        assert_eq!(interrupts.flag & Interrupt::Timer as u8, 0);
+
+        tick(&mut timer, &mut interrupts, 1);
+        assert_eq!(timer.read(TIMA), 0x42);
+        assert_ne!(interrupts.flag & Interrupt::Timer as u8, 0);
+
+        // TIMA ignores writes while it is being reloaded
+        timer.write(TIMA, 0x10);
+        assert_eq!(timer.read(TIMA), 0x42);
+    }
+
+    #[test]
+    fn write_after_overflow_cancels_reload() {
+        let mut timer = Timer::new();
+        let mut interrupts = Interrupts::new();
+        timer.write(TAC, 0b101);
+        timer.write(TMA, 0x42);
+        timer.write(TIMA, 0xFF);
+
+        tick(&mut timer, &mut interrupts, 4);
+        timer.write(TIMA, 0x10);
+        tick(&mut timer, &mut interrupts, 1);
+        assert_eq!(timer.read(TIMA), 0x10);
+        assert_eq!(interrupts.flag & Interrupt::Timer as u8, 0);
+    }
+
+    #[test]
+    fn div_write_ticks_tima_on_falling_edge() {
+        let mut timer = Timer::new();
+        let mut interrupts = Interrupts::new();
+        // enabled, every 64 M-cycles: bit 7 of the counter
+        timer.write(TAC, 0b111);
+        tick(&mut timer, &mut interrupts, 32);
+        assert_eq!(timer.read(TIMA), 0);
+
+        timer.write(DIV, 0x00);
+        assert_eq!(timer.read(TIMA), 1);
+        assert_eq!(timer.read(DIV), 0);
+    }
+}
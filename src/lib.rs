@@ -3,6 +3,13 @@ extern crate core;
 #[cfg(target_family = "wasm")]
 use wasm_bindgen::prelude::*;
 
+#[cfg(target_family = "wasm")]
+use crate::hardware::cartridge::Cartridge;
+#[cfg(target_family = "wasm")]
+use crate::hardware::gameboy::GameBoy;
+#[cfg(target_family = "wasm")]
+use crate::hardware::joypad::Button;
+
 pub mod utils;
 pub mod hardware;
 
@@ -23,4 +30,45 @@ extern {
 #[wasm_bindgen]
 pub fn greet() {
     alert("Hello, gameboy_rust_webassembly_emulator!");
-}
\ No newline at end of file
+}
+
+// the page's handle on the same GameBoy the egui app drives
+#[cfg(target_family = "wasm")]
+#[wasm_bindgen]
+pub struct Emulator {
+    gameboy: GameBoy,
+}
+
+#[cfg(target_family = "wasm")]
+#[wasm_bindgen]
+impl Emulator {
+    // rom is the whole image, as read from a file input
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8]) -> Result<Emulator, JsValue> {
+        let cartridge = Cartridge::from_bytes(rom.to_vec()).map_err(|error| JsValue::from_str(&error.to_string()))?;
+        Ok(Emulator { gameboy: GameBoy::new(cartridge) })
+    }
+
+    // returns the M-cycles it took
+    pub fn run_frame(&mut self) -> usize {
+        let cycles = self.gameboy.run_frame();
+        // no rumble in the browser, draining only keeps the events from piling up
+        self.gameboy.cartridge_mut().drain_rumble_events();
+        cycles
+    }
+
+    // one shade (0-3) per pixel, 160 by 144, copied out as a Uint8Array
+    pub fn framebuffer(&self) -> Vec<u8> {
+        self.gameboy.framebuffer().to_vec()
+    }
+
+    // interleaved stereo samples since the last call, as a Float32Array
+    pub fn audio_samples(&mut self) -> Vec<f32> {
+        self.gameboy.audio_samples()
+    }
+
+    // the page maps its own keys, like MyApp does
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        self.gameboy.set_button(button, pressed);
+    }
+}
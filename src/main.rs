@@ -1,98 +1,193 @@
 use std::path::Path;
-use std::{thread, time};
-use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use eframe::egui;
 
+use gameboy_rust_webassembly_emulator::hardware::boot_rom::BootROM;
 use gameboy_rust_webassembly_emulator::hardware::cartridge::Cartridge;
-use gameboy_rust_webassembly_emulator::hardware::cpu::{CPU, instructions};
-use gameboy_rust_webassembly_emulator::hardware::cpu::instructions::{Instruction, Target};
-use gameboy_rust_webassembly_emulator::hardware::cpu::memory::Memory;
 use gameboy_rust_webassembly_emulator::hardware::cpu::registers::flags::Flag;
-use gameboy_rust_webassembly_emulator::hardware::cpu::registers::Registers;
+use gameboy_rust_webassembly_emulator::hardware::gameboy::GameBoy;
+use gameboy_rust_webassembly_emulator::hardware::joypad::Button;
+use gameboy_rust_webassembly_emulator::hardware::model::Model;
+use gameboy_rust_webassembly_emulator::hardware::ppu::{DMG_PALETTE, SCREEN_HEIGHT, SCREEN_WIDTH};
 
-fn main() {
-    let cartridge = Cartridge::load(Path::new("roms/tetris.gb"));
-    println!("TITLE: {}", cartridge.get_title());
-    println!("ROM_SIZE: {:?}", cartridge.get_header());
+const SCALE: f32 = 3.0;
+// how often battery-backed RAM is flushed to the .sav file while it keeps changing
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
 
-    let mut cpu = CPU::new(cartridge);
-    println!("yo {:X} {:X}", cpu.bus_read(0x216), cpu.bus_read(0x217));
+const USAGE: &str = "usage: gameboy_rust_webassembly_emulator [--boot | --boot=<path>] [rom]";
 
-    let running_cpu = thread::spawn(move || {
-        while cpu.is_running {
-            cpu.step();
+const KEYMAP: [(egui::Key, Button); 8] = [
+    (egui::Key::ArrowRight, Button::Right),
+    (egui::Key::ArrowLeft, Button::Left),
+    (egui::Key::ArrowUp, Button::Up),
+    (egui::Key::ArrowDown, Button::Down),
+    (egui::Key::X, Button::A),
+    (egui::Key::Z, Button::B),
+    (egui::Key::Backspace, Button::Select),
+    (egui::Key::Enter, Button::Start),
+];
+
+fn main() {
+    // --boot runs the bundled boot ROM first, --boot=<path> an external image
+    let mut path = None;
+    let mut boot = None;
+    for arg in std::env::args().skip(1) {
+        if arg == "--boot" {
+            boot = Some(None);
+        } else if let Some(boot_path) = arg.strip_prefix("--boot=") {
+            boot = Some(Some(boot_path.to_string()));
+        } else if arg.starts_with("--") || path.is_some() {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        } else {
+            path = Some(arg);
+        }
+    }
+    let path = path.unwrap_or_else(|| "roms/tetris.gb".to_string());
+    let cartridge = match Cartridge::load(Path::new(&path)) {
+        Ok(cartridge) => cartridge,
+        Err(error) => {
+            eprintln!("{}: {}", path, error);
+            std::process::exit(1);
+        }
+    };
+    println!("TITLE: {}", cartridge.get_title());
+    let header = cartridge.get_header();
+    println!("TYPE: {:?}", header.cartridge_type());
+    println!("ROM_SIZE: {:?}, RAM_SIZE: {:?}", header.rom_size(), header.ram_size());
+    println!("LICENSEE: {}", header.licensee().unwrap_or("unknown"));
+    println!("HEADER: {}", cartridge.validate());
 
-            if cpu.registers.pc == 0x21B {
-                cpu.stop();
+    let model = Model::default();
+    let gameboy = match boot {
+        Some(None) => {
+            let boot_rom = BootROM::replacement(model, &cartridge.header);
+            GameBoy::with_boot_rom(cartridge, boot_rom)
+        }
+        Some(Some(boot_path)) => {
+            let boot_rom = std::fs::read(&boot_path)
+                .map_err(|error| error.to_string())
+                .and_then(|data| BootROM::new(model, data).map_err(|error| error.to_string()));
+            match boot_rom {
+                Ok(boot_rom) => GameBoy::with_boot_rom(cartridge, boot_rom),
+                Err(error) => {
+                    eprintln!("{}: {}", boot_path, error);
+                    std::process::exit(1);
+                }
             }
-            sleep(Duration::from_millis(100));
         }
-    });
-
-    // let options = eframe::NativeOptions::default();
-    // eframe::run_native(
-    //     "My emulator",
-    //     options,
-    //     Box::new(|_cc| Box::new(MyApp::new(cpu))),
-    // );
-    println!("yoooooo");
-    running_cpu.join().unwrap();
+        None => GameBoy::with_model(cartridge, model),
+    };
+
+    let options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "My emulator",
+        options,
+        Box::new(|_cc| Box::new(MyApp::new(gameboy))),
+    );
 }
 
 struct MyApp {
-    cpu: CPU,
+    gameboy: GameBoy,
+    screen: Option<egui::TextureHandle>,
+    paused: bool,
+    last_save: Instant,
 }
 
 impl MyApp {
-    pub fn new(cpu: CPU) -> Self {
+    pub fn new(gameboy: GameBoy) -> Self {
         Self {
-            cpu
+            gameboy,
+            screen: None,
+            paused: false,
+            last_save: Instant::now(),
+        }
+    }
+
+    fn save(&mut self) {
+        if let Err(error) = self.gameboy.cartridge_mut().save() {
+            eprintln!("Could not save: {}", error);
+        }
+        self.last_save = Instant::now();
+    }
+
+    fn handle_input(&mut self, ctx: &egui::Context) {
+        let input = ctx.input();
+        for (key, button) in KEYMAP {
+            self.gameboy.set_button(button, input.key_down(key));
         }
     }
+
+    fn screen_image(&self) -> egui::ColorImage {
+        let mut rgba = Vec::with_capacity(SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+        for &shade in self.gameboy.framebuffer() {
+            let [r, g, b] = DMG_PALETTE[shade as usize];
+            rgba.extend_from_slice(&[r, g, b, 0xFF]);
+        }
+        egui::ColorImage::from_rgba_unmultiplied([SCREEN_WIDTH, SCREEN_HEIGHT], &rgba)
+    }
 }
 
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            // ui.heading("My egui Application");
-            // ui.horizontal(|ui| {
-            //     ui.label("Your name: ");
-            //     ui.text_edit_singleline(&mut self.name);
-            // });
-            // ui.add(egui::Slider::new(&mut self.age, 0..=120).text("age"));
-            // if ui.button("Click each year").clicked() {
-            //     self.age += 1;
-            // }
-            // ui.label(format!("Hello '{}', age {}", self.name, self.age));
+        self.handle_input(ctx);
+        if !self.paused {
+            self.gameboy.run_frame();
+            // there's no motor to drive here, draining only keeps the events from piling up
+            self.gameboy.cartridge_mut().drain_rumble_events();
+            ctx.request_repaint();
+        }
+        if self.gameboy.cartridge_mut().needs_saving() && self.last_save.elapsed() >= SAVE_INTERVAL {
+            self.save();
+        }
+
+        let image = self.screen_image();
+        match &mut self.screen {
+            Some(screen) => screen.set(image),
+            None => self.screen = Some(ctx.load_texture("screen", image)),
+        }
 
+        egui::SidePanel::right("registers").show(ctx, |ui| {
             ui.label("Registers:");
-            ui.label(format!("A: 0x{:X}", self.cpu.registers.a));
+            ui.label(format!("A: 0x{:X}", self.gameboy.cpu().registers.a));
             ui.horizontal(|ui| {
                 ui.label("F:");
-                ui.checkbox(&mut self.cpu.registers.get_flag(Flag::Zero), "Zero");
-                ui.checkbox(&mut self.cpu.registers.get_flag(Flag::Negative), "Negative");
-                ui.checkbox(&mut self.cpu.registers.get_flag(Flag::HalfCarry), "HalfCarry");
-                ui.checkbox(&mut self.cpu.registers.get_flag(Flag::Carry), "Carry");
+                ui.checkbox(&mut self.gameboy.cpu().registers.get_flag(Flag::Zero), "Zero");
+                ui.checkbox(&mut self.gameboy.cpu().registers.get_flag(Flag::Negative), "Negative");
+                ui.checkbox(&mut self.gameboy.cpu().registers.get_flag(Flag::HalfCarry), "HalfCarry");
+                ui.checkbox(&mut self.gameboy.cpu().registers.get_flag(Flag::Carry), "Carry");
             });
-            ui.label(format!("B: 0x{:X}", self.cpu.registers.b));
-            ui.label(format!("C: 0x{:X}", self.cpu.registers.c));
-            ui.label(format!("D: 0x{:X}", self.cpu.registers.d));
-            ui.label(format!("E: 0x{:X}", self.cpu.registers.e));
-            ui.label(format!("H: 0x{:X}", self.cpu.registers.h));
-            ui.label(format!("L: 0x{:X}", self.cpu.registers.l));
-            ui.label(format!("AF: 0x{:X}", self.cpu.registers.get_af()));
-            ui.label(format!("BC: 0x{:X}", self.cpu.registers.get_bc()));
-            ui.label(format!("DE: 0x{:X}", self.cpu.registers.get_de()));
-            ui.label(format!("HL: 0x{:X}", self.cpu.registers.get_hl()));
-            ui.label(format!("SP: 0x{:X}", self.cpu.registers.sp));
-            ui.label(format!("PC: 0x{:X}", self.cpu.registers.pc));
-            ui.checkbox(&mut self.cpu.get_ime(), "IME");
+            ui.label(format!("B: 0x{:X}", self.gameboy.cpu().registers.b));
+            ui.label(format!("C: 0x{:X}", self.gameboy.cpu().registers.c));
+            ui.label(format!("D: 0x{:X}", self.gameboy.cpu().registers.d));
+            ui.label(format!("E: 0x{:X}", self.gameboy.cpu().registers.e));
+            ui.label(format!("H: 0x{:X}", self.gameboy.cpu().registers.h));
+            ui.label(format!("L: 0x{:X}", self.gameboy.cpu().registers.l));
+            ui.label(format!("AF: 0x{:X}", self.gameboy.cpu().registers.get_af()));
+            ui.label(format!("BC: 0x{:X}", self.gameboy.cpu().registers.get_bc()));
+            ui.label(format!("DE: 0x{:X}", self.gameboy.cpu().registers.get_de()));
+            ui.label(format!("HL: 0x{:X}", self.gameboy.cpu().registers.get_hl()));
+            ui.label(format!("SP: 0x{:X}", self.gameboy.cpu().registers.sp));
+            ui.label(format!("PC: 0x{:X}", self.gameboy.cpu().registers.pc));
+            ui.checkbox(&mut self.gameboy.cpu().get_ime(), "IME");
 
+            ui.checkbox(&mut self.paused, "Paused");
             if ui.button("Step").clicked() {
-                self.cpu.step();
+                self.gameboy.step_instruction();
+            }
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(screen) = &self.screen {
+                ui.image(screen.id(), [SCREEN_WIDTH as f32 * SCALE, SCREEN_HEIGHT as f32 * SCALE]);
             }
         });
     }
-}
\ No newline at end of file
+
+    fn on_exit(&mut self, _gl: &eframe::glow::Context) {
+        if self.gameboy.cartridge_mut().has_save() {
+            self.save();
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! Runs the sm83 SingleStepTests (https://github.com/SingleStepTests/sm83) against the CPU core.
+//!
+//! A few hand-written cases in the suite's format are checked in under `tests/data/sm83` and always
+//! run. The full suite isn't bundled, point `SM83_TESTS_DIR` at a checkout's `v1` directory or copy
+//! it into `tests/sm83` and run `cargo test --test sm83 -- --ignored`.
+//!
+//! The suite treats the opcode as already fetched: it sits at `pc - 1`, and the last cycle of
+//! every case is the fetch of the next opcode. The harness starts the CPU at the opcode instead
+//! and does that last fetch itself after the step.
+
+#![cfg(not(target_family = "wasm"))]
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use gameboy_rust_webassembly_emulator::hardware::cpu::bus::Bus;
+use gameboy_rust_webassembly_emulator::hardware::cpu::interrupts::{INTERRUPT_ENABLE, INTERRUPT_FLAG, Interrupt};
+use gameboy_rust_webassembly_emulator::hardware::cpu::CPU;
+
+const DEFAULT_DIR: &str = "tests/sm83";
+const FIXTURE_DIR: &str = "tests/data/sm83";
+
+// what the CPU did with the bus during one M-cycle, None when it didn't touch it
+type Cycle = Option<(u16, u8, &'static str)>;
+
+// flat memory that records every access, one entry per M-cycle
+struct RecordingBus {
+    memory: Vec<u8>,
+    access: Cycle,
+    cycles: Vec<Cycle>,
+}
+
+impl RecordingBus {
+    fn new() -> Self {
+        RecordingBus { memory: vec![0; 0x10000], access: None, cycles: Vec::new() }
+    }
+}
+
+impl Bus for RecordingBus {
+    fn read(&mut self, address: u16) -> u8 {
+        let value = self.memory[address as usize];
+        self.access = Some((address, value, "r-m"));
+        value
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.memory[address as usize] = value;
+        self.access = Some((address, value, "-wm"));
+    }
+
+    fn tick(&mut self) {
+        self.cycles.push(self.access.take());
+    }
+
+    // checking for interrupts isn't bus activity
+    fn interrupt_enable(&mut self) -> u8 {
+        self.memory[INTERRUPT_ENABLE as usize]
+    }
+
+    fn interrupt_flag(&mut self) -> u8 {
+        self.memory[INTERRUPT_FLAG as usize]
+    }
+
+    fn acknowledge_interrupt(&mut self, interrupt: Interrupt) {
+        self.memory[INTERRUPT_FLAG as usize] &= !(interrupt as u8);
+    }
+
+    // nor is STOP sampling the joypad, nothing is pressed
+    fn joypad_lines(&mut self) -> u8 {
+        0x0F
+    }
+}
+
+fn number(state: &Value, key: &str) -> u16 {
+    state[key].as_u64().unwrap_or_else(|| panic!("missing {}", key)) as u16
+}
+
+fn ram(state: &Value) -> Vec<(u16, u8)> {
+    state["ram"]
+        .as_array()
+        .expect("missing ram")
+        .iter()
+        .map(|entry| (entry[0].as_u64().unwrap() as u16, entry[1].as_u64().unwrap() as u8))
+        .collect()
+}
+
+fn set_up(cpu: &mut CPU, bus: &mut RecordingBus, state: &Value) {
+    let registers = &mut cpu.registers;
+    registers.a = number(state, "a") as u8;
+    registers.f = number(state, "f") as u8 & 0xF0;
+    registers.b = number(state, "b") as u8;
+    registers.c = number(state, "c") as u8;
+    registers.d = number(state, "d") as u8;
+    registers.e = number(state, "e") as u8;
+    registers.h = number(state, "h") as u8;
+    registers.l = number(state, "l") as u8;
+    registers.sp = number(state, "sp");
+    // at the opcode, which the suite has already fetched
+    registers.pc = number(state, "pc").wrapping_sub(1);
+    cpu.ime = state["ime"].as_u64() == Some(1);
+    if let Some(ie) = state["ie"].as_u64() {
+        bus.memory[INTERRUPT_ENABLE as usize] = ie as u8;
+    }
+    for (address, value) in ram(state) {
+        bus.memory[address as usize] = value;
+    }
+}
+
+// the fetch of the next opcode that overlaps the end of every instruction
+fn prefetch(cpu: &mut CPU, bus: &mut RecordingBus) {
+    let pc = cpu.registers.pc;
+    bus.read(pc);
+    cpu.registers.pc = pc.wrapping_add(1);
+    bus.tick();
+}
+
+// every difference from the expected final state, empty when the case passes
+fn compare(cpu: &CPU, bus: &RecordingBus, state: &Value, cycles: &[Value]) -> Vec<String> {
+    let mut differences = Vec::new();
+    let registers = &cpu.registers;
+    let actual = [
+        ("a", registers.a as u16),
+        ("f", registers.f as u16),
+        ("b", registers.b as u16),
+        ("c", registers.c as u16),
+        ("d", registers.d as u16),
+        ("e", registers.e as u16),
+        ("h", registers.h as u16),
+        ("l", registers.l as u16),
+        ("sp", registers.sp),
+        ("pc", registers.pc),
+    ];
+    for (name, value) in actual {
+        let expected = number(state, name);
+        if value != expected {
+            differences.push(format!("{} is {:04X}, expected {:04X}", name, value, expected));
+        }
+    }
+    if let Some(ime) = state["ime"].as_u64() {
+        if cpu.ime != (ime == 1) {
+            differences.push(format!("ime is {}, expected {}", cpu.ime, ime == 1));
+        }
+    }
+    for (address, expected) in ram(state) {
+        let value = bus.memory[address as usize];
+        if value != expected {
+            differences.push(format!("[{:04X}] is {:02X}, expected {:02X}", address, value, expected));
+        }
+    }
+
+    let expected: Vec<Cycle> = cycles
+        .iter()
+        .map(|cycle| match (cycle[0].as_u64(), cycle[1].as_u64(), cycle[2].as_str()) {
+            (Some(address), Some(value), Some("r-m")) => Some((address as u16, value as u8, "r-m")),
+            (Some(address), Some(value), Some("-wm")) => Some((address as u16, value as u8, "-wm")),
+            _ => None,
+        })
+        .collect();
+    // the first cycle is the opcode fetch the suite leaves out
+    let actual = &bus.cycles[1.min(bus.cycles.len())..];
+    if actual != expected {
+        differences.push(format!("bus activity was {:?}, expected {:?}", actual, expected));
+    }
+    differences
+}
+
+struct Report {
+    passed: usize,
+    failed: usize,
+    // the first failing case, enough to start debugging from
+    first_failure: Option<String>,
+}
+
+fn run_file(path: &Path) -> Report {
+    let text = fs::read_to_string(path).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+    let cases: Vec<Value> = serde_json::from_str(&text).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+    let mut report = Report { passed: 0, failed: 0, first_failure: None };
+
+    for case in &cases {
+        let mut cpu = CPU::new();
+        let mut bus = RecordingBus::new();
+        set_up(&mut cpu, &mut bus, &case["initial"]);
+        // an unimplemented opcode can panic, that only fails its own case, quietly
+        let panic_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| {}));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            cpu.step(&mut bus);
+            prefetch(&mut cpu, &mut bus);
+            (cpu, bus)
+        }));
+        std::panic::set_hook(panic_hook);
+        let differences = match result {
+            Ok((cpu, bus)) => compare(&cpu, &bus, &case["final"], case["cycles"].as_array().map_or(&[], |cycles| cycles)),
+            Err(_) => vec!["panicked".to_string()],
+        };
+
+        if differences.is_empty() {
+            report.passed += 1;
+        } else {
+            report.failed += 1;
+            if report.first_failure.is_none() {
+                let name = case["name"].as_str().unwrap_or("?");
+                report.first_failure = Some(format!("{}: {}", name, differences.join(", ")));
+            }
+        }
+    }
+    report
+}
+
+fn tests_dir() -> PathBuf {
+    std::env::var_os("SM83_TESTS_DIR").map_or_else(|| PathBuf::from(DEFAULT_DIR), PathBuf::from)
+}
+
+fn run_suite(dir: &Path) {
+    let files: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap_or_else(|error| panic!("{}: {}", dir.display(), error))
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .collect();
+    assert!(!files.is_empty(), "no sm83 tests in {}", dir.display());
+
+    // keyed by opcode, "cb 11" for prefixed ones, as the files are named
+    let reports: BTreeMap<String, Report> = files
+        .iter()
+        .map(|path| (path.file_stem().unwrap().to_string_lossy().to_string(), run_file(path)))
+        .collect();
+
+    let failing: Vec<(&String, &Report)> = reports.iter().filter(|(_, report)| report.failed > 0).collect();
+    for (opcode, report) in &failing {
+        eprintln!(
+            "{}: {} of {} failed, first {}",
+            opcode,
+            report.failed,
+            report.passed + report.failed,
+            report.first_failure.as_deref().unwrap_or("")
+        );
+    }
+    eprintln!("{} of {} opcodes pass", reports.len() - failing.len(), reports.len());
+    assert!(failing.is_empty(), "{} opcodes fail", failing.len());
+}
+
+#[test]
+fn sm83_fixture() {
+    run_suite(&Path::new(env!("CARGO_MANIFEST_DIR")).join(FIXTURE_DIR));
+}
+
+#[test]
+#[ignore = "needs the sm83 JSON files in SM83_TESTS_DIR or tests/sm83"]
+fn sm83_single_step_tests() {
+    let dir = tests_dir();
+    assert!(dir.is_dir(), "no sm83 tests in {}, set SM83_TESTS_DIR to run them", dir.display());
+    run_suite(&dir);
+}
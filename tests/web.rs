@@ -6,18 +6,19 @@ extern crate wasm_bindgen_test;
 
 use wasm_bindgen_test::*;
 
-use gameboy_rust_webassembly_emulator::hardware::cpu::memory::{MAX_RAM, Memory};
+use gameboy_rust_webassembly_emulator::hardware::cpu::memory::{HRAM_SIZE, Memory, WRAM_SIZE};
 
 wasm_bindgen_test_configure!(run_in_browser);
 
 #[wasm_bindgen_test]
-fn assert_memory_max() {
-    assert_eq!(MAX_RAM, 65535)
+fn assert_memory_region_sizes() {
+    assert_eq!(WRAM_SIZE, 0x2000);
+    assert_eq!(HRAM_SIZE, 0x7F);
 }
 
 #[wasm_bindgen_test]
 fn assert_memory_cells_size() {
     let memory = Memory::new();
-    assert_eq!(memory.length(), MAX_RAM)
+    assert_eq!(memory.wram.len(), WRAM_SIZE);
+    assert_eq!(memory.hram.len(), HRAM_SIZE);
 }
-